@@ -0,0 +1,243 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs;
+
+use crate::client::{self, WhisperClient};
+use crate::error::MurmurError;
+use crate::media;
+use crate::request::TranscriptionRequest;
+use crate::retry::RetryPolicy;
+use crate::transcription::Transcript;
+use crate::utils::{self, Config};
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+pub const WHISPER_CPP_BASE_URL: &str = "http://127.0.0.1:8080";
+
+/// Available transcription backends
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// OpenAI Whisper API
+    #[default]
+    #[value(name = "openai")]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Any server implementing the OpenAI `/audio/transcriptions` endpoint
+    #[value(name = "openai-compatible")]
+    #[serde(rename = "openai-compatible")]
+    OpenAiCompatible,
+    /// Local whisper.cpp server (`whisper-server`)
+    #[value(name = "whisper-cpp")]
+    #[serde(rename = "whisper-cpp")]
+    WhisperCpp,
+}
+
+impl BackendKind {
+    /// Default base URL for the backend, if it has one
+    pub fn default_base_url(&self) -> Option<&'static str> {
+        match self {
+            BackendKind::OpenAi => Some(OPENAI_BASE_URL),
+            BackendKind::OpenAiCompatible => None,
+            BackendKind::WhisperCpp => Some(WHISPER_CPP_BASE_URL),
+        }
+    }
+
+    /// Name of the backend as given with `--backend`
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendKind::OpenAi => "openai",
+            BackendKind::OpenAiCompatible => "openai-compatible",
+            BackendKind::WhisperCpp => "whisper-cpp",
+        }
+    }
+
+    /// Whether the backend needs an API key to transcribe
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, BackendKind::WhisperCpp)
+    }
+}
+
+/// A service that can turn an audio file into text
+#[async_trait]
+pub trait TranscriptionBackend: Send + Sync {
+    /// Short name used in log and error messages
+    fn name(&self) -> &str;
+
+    /// Transcribe `audio` as described by `request`, with segment timestamps
+    /// when `request.format` needs them. `audio` is the request's input file,
+    /// or a chunk or transcoded copy of it.
+    async fn transcribe(&self, audio: &Path, request: &TranscriptionRequest) -> Result<Transcript>;
+
+    /// Number of rate-limited responses seen so far, used to adapt concurrency
+    fn rate_limit_count(&self) -> u64 {
+        0
+    }
+}
+
+/// Create the transcription backend selected in the config
+pub fn create_backend(api_key: &str, config: &Config) -> Result<Box<dyn TranscriptionBackend>> {
+    let base_url = config
+        .base_url
+        .clone()
+        .or_else(|| config.backend.default_base_url().map(str::to_string))
+        .context("A base URL is required for the openai-compatible backend (use --base-url)")?;
+
+    let backend: Box<dyn TranscriptionBackend> = match config.backend {
+        BackendKind::OpenAi | BackendKind::OpenAiCompatible => Box::new(
+            WhisperClient::with_base_url(api_key.to_string(), base_url, config)?,
+        ),
+        BackendKind::WhisperCpp => Box::new(WhisperCppClient::new(base_url, config)?),
+    };
+
+    Ok(backend)
+}
+
+/// Client for a local whisper.cpp server
+pub struct WhisperCppClient {
+    client: reqwest::Client,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl WhisperCppClient {
+    pub fn new(base_url: String, config: &Config) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(
+                config.whisper_timeout_seconds,
+            ))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::new(config),
+        })
+    }
+
+    fn build_form(
+        &self,
+        file_name: &str,
+        file_bytes: Vec<u8>,
+        request: &TranscriptionRequest,
+    ) -> Result<Form> {
+        let form = client::request_fields(Form::new(), request);
+
+        let file_part = Part::bytes(file_bytes)
+            .file_name(file_name.to_string())
+            .mime_str(media::mime_type(Path::new(file_name)))?;
+
+        Ok(form.part("file", file_part))
+    }
+}
+
+#[async_trait]
+impl TranscriptionBackend for WhisperCppClient {
+    fn name(&self) -> &str {
+        "whisper.cpp"
+    }
+
+    fn rate_limit_count(&self) -> u64 {
+        self.retry.rate_limit_count()
+    }
+
+    async fn transcribe(&self, audio: &Path, request: &TranscriptionRequest) -> Result<Transcript> {
+        if !audio.exists() {
+            return Err(MurmurError::UnsupportedInput(format!(
+                "Input file {:?} does not exist",
+                audio
+            ))
+            .into());
+        }
+
+        let file_bytes = fs::read(audio).await.context("Failed to read audio file")?;
+
+        let file_name = utils::get_filename_or_default(audio, "audio.mp3");
+        let response = self
+            .retry
+            .send("whisper.cpp transcription", || {
+                let form = self.build_form(&file_name, file_bytes.clone(), request)?;
+                Ok(self
+                    .client
+                    .post(format!("{}/inference", self.base_url))
+                    .multipart(form))
+            })
+            .await?;
+
+        let text = WhisperClient::handle_api_response(response, "transcription").await?;
+        if request.format.needs_segments() {
+            Transcript::from_verbose_json(&text)
+        } else {
+            Ok(Transcript::from_text(text.trim()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_whisper_cpp_client() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/inference"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(" Local transcription.\n"))
+            .mount(&mock_server)
+            .await;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[0u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+
+        let config = Config {
+            backend: BackendKind::WhisperCpp,
+            base_url: Some(mock_server.uri()),
+            ..Config::default()
+        };
+        let backend = create_backend("", &config).unwrap();
+
+        let request = TranscriptionRequest::file(temp_file.path())
+            .language("en")
+            .build();
+
+        let result = backend.transcribe(temp_file.path(), &request).await;
+        assert_eq!(result.unwrap().text, "Local transcription.");
+    }
+
+    #[test]
+    fn test_create_backend_selection() {
+        let config = Config::default();
+        assert_eq!(create_backend("key", &config).unwrap().name(), "openai");
+
+        let config = Config {
+            backend: BackendKind::WhisperCpp,
+            ..Config::default()
+        };
+        assert_eq!(create_backend("", &config).unwrap().name(), "whisper.cpp");
+
+        // The compatible backend has no default endpoint
+        let config = Config {
+            backend: BackendKind::OpenAiCompatible,
+            ..Config::default()
+        };
+        assert!(create_backend("key", &config).is_err());
+
+        let config = Config {
+            backend: BackendKind::OpenAiCompatible,
+            base_url: Some("http://localhost:9000/v1".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(
+            create_backend("key", &config).unwrap().name(),
+            "openai-compatible"
+        );
+    }
+}
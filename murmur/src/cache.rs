@@ -1,8 +1,141 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::fs::TryLockError;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tokio::fs;
 
-use crate::utils::{self, Config, FileMetadata};
+use crate::backend::{BackendKind, OPENAI_BASE_URL};
+use crate::chunking::AudioChunk;
+use crate::client;
+use crate::error::MurmurError;
+use crate::request::TranscriptionRequest;
+use crate::transcription::Transcript;
+use crate::utils::{self, ChunkRecord, ChunkStatus, Config, FileMetadata};
+
+/// Subdirectory of the cache directory holding one directory per input file
+pub const CHUNKS_DIR_NAME: &str = "chunks";
+/// Subdirectory holding finished transcripts of whole input files
+pub const TRANSCRIPTS_DIR_NAME: &str = "transcripts";
+/// Subdirectory holding enhancement results
+pub const ENHANCED_DIR_NAME: &str = "enhanced";
+pub const LOCK_FILE: &str = "lock";
+/// Start of the header line of every cache file
+const CHECKSUM_PREFIX: &str = "sha256:";
+
+/// `murmur cache` actions
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum CacheCommand {
+    /// List cached files, oldest first
+    List,
+    /// Show how much is cached and how much space it takes
+    Stats,
+    /// Remove entries older than a given age or beyond a total size, oldest first
+    #[command(group(
+        clap::ArgGroup::new("limit")
+            .required(true)
+            .multiple(true)
+            .args(["older_than", "max_size"])
+    ))]
+    Prune {
+        /// Remove entries not used for this long (e.g. '12h', '30d', '2w')
+        #[arg(long, value_parser = parse_age)]
+        older_than: Option<Duration>,
+        /// Remove the oldest entries until the cache fits (e.g. '500M', '2G')
+        #[arg(long, value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Remove everything from the cache
+    Clear,
+}
+
+/// What a cache entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEntryKind {
+    /// Chunks and chunk transcripts of a file that is unfinished or still running
+    Chunks,
+    /// Finished transcript of a whole file
+    Transcript,
+    /// Enhancement result
+    Enhancement,
+}
+
+impl CacheEntryKind {
+    fn label(&self) -> &'static str {
+        match self {
+            CacheEntryKind::Chunks => "chunks",
+            CacheEntryKind::Transcript => "transcript",
+            CacheEntryKind::Enhancement => "enhanced",
+        }
+    }
+}
+
+/// A removable item in the cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub kind: CacheEntryKind,
+    pub path: PathBuf,
+    /// Name of the input file, when known
+    pub name: Option<String>,
+    pub chunk_count: Option<usize>,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// Last time anything in the entry was written
+    pub modified: SystemTime,
+}
+
+/// What a prune or clear removed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub removed: usize,
+    pub freed: u64,
+    /// Chunk directories left alone because a running murmur holds their lock
+    pub in_use: usize,
+}
+
+/// Transcription settings that change what comes back for the same audio
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TranscriptionParams {
+    pub backend: BackendKind,
+    pub base_url: Option<String>,
+    pub model: String,
+    pub language: Option<String>,
+    pub response_format: &'static str,
+    /// Prompt sent along with the audio, if any
+    pub prompt: Option<String>,
+    /// Left out of the key when unset, so existing cache entries still match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Whether chunks are prompted with the previous chunk's transcript; left
+    /// out of the key when off, like `temperature`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub chain_chunks: bool,
+}
+
+impl TranscriptionParams {
+    pub fn new(config: &Config, request: &TranscriptionRequest) -> Self {
+        Self {
+            backend: config.backend,
+            base_url: config.base_url.clone(),
+            model: request
+                .model
+                .clone()
+                .unwrap_or_else(|| config.transcription_model.clone()),
+            language: request.language.clone(),
+            response_format: client::response_format(request.format),
+            prompt: request.prompt.clone(),
+            temperature: request.temperature,
+            chain_chunks: config.chain_chunks,
+        }
+    }
+
+    /// Short hash identifying these parameters in cache file names
+    pub fn cache_key(&self) -> String {
+        let params = serde_json::to_string(self).unwrap_or_default();
+        sha256::digest(params)[..16].to_string()
+    }
+}
 
 /// Cache management for audio chunks and transcripts
 pub struct CacheManager {
@@ -16,162 +149,781 @@ impl CacheManager {
         }
     }
 
-    /// Validate existing cache and cleanup if hash doesn't match
-    pub async fn validate_and_cleanup_if_needed(&self, current_hash: &str) -> Result<()> {
-        let metadata_path = self.get_metadata_path();
+    /// Cache directory for an input file, keyed by its hash and the settings
+    /// that decide how it is cut into chunks
+    pub fn job_dir(&self, file_hash: &str) -> PathBuf {
+        self.config
+            .cache_dir
+            .join(CHUNKS_DIR_NAME)
+            .join(self.audio_key(file_hash))
+    }
 
-        if Path::new(&metadata_path).exists() {
-            match self.read_existing_metadata(&metadata_path).await {
-                Ok(existing_metadata) => {
-                    if existing_metadata.original_hash != current_hash {
-                        self.cleanup_all_cached_files().await?;
-                    }
-                }
-                Err(_) => {
-                    self.cleanup_all_cached_files().await?;
-                }
+    /// Key for the audio we send for an input file: its hash plus the
+    /// pre-processing and chunking settings
+    fn audio_key(&self, file_hash: &str) -> String {
+        let config = &self.config;
+        let key = format!(
+            "{}|chunk={}|grace={}|silence={}:{}:{}:{}|preprocess={:?}:{}",
+            file_hash,
+            config.chunk_size_mb,
+            config.grace_period_seconds,
+            config.silence_detection,
+            config.silence_noise_db,
+            config.silence_min_seconds,
+            config.silence_search_seconds,
+            config.preprocess,
+            config.preprocess_bitrate_kbps,
+        );
+        sha256::digest(key)
+    }
+
+    /// Cached transcript of a whole input file made with the same parameters
+    pub async fn get_file_transcript(
+        &self,
+        file_hash: &str,
+        params_key: &str,
+    ) -> Result<Option<Transcript>> {
+        let path = self.file_transcript_path(file_hash, params_key);
+        let entry = read_cache_file(&path)
+            .await
+            .and_then(|content| serde_json::from_str::<FileTranscriptEntry>(&content).ok());
+
+        Ok(entry
+            .map(|entry| entry.transcript)
+            .filter(|transcript| !transcript.text.trim().is_empty()))
+    }
+
+    /// Save the transcript of a whole input file
+    pub async fn save_file_transcript(
+        &self,
+        file_path: &Path,
+        file_hash: &str,
+        params_key: &str,
+        transcript: &Transcript,
+    ) -> Result<()> {
+        let entry = FileTranscriptEntry {
+            original_filename: utils::get_filename_or_default(file_path, "unknown_file"),
+            original_size: utils::get_file_size(file_path).await?,
+            transcript: transcript.clone(),
+        };
+
+        let path = self.file_transcript_path(file_hash, params_key);
+        write_cache_file(&path, &serde_json::to_string(&entry)?).await
+    }
+
+    fn file_transcript_path(&self, file_hash: &str, params_key: &str) -> PathBuf {
+        self.config
+            .cache_dir
+            .join(TRANSCRIPTS_DIR_NAME)
+            .join(format!("{}.{}.json", self.audio_key(file_hash), params_key))
+    }
+
+    /// Cached result of sending `prompt` to the enhancement model at `base_url`
+    pub async fn get_enhanced_text(
+        &self,
+        base_url: &str,
+        model: &str,
+        prompt: &str,
+    ) -> Option<String> {
+        read_cache_file(&self.enhanced_text_path(base_url, model, prompt))
+            .await
+            .filter(|text| !text.trim().is_empty())
+    }
+
+    /// Save the result of sending `prompt` to the enhancement model at `base_url`
+    pub async fn save_enhanced_text(
+        &self,
+        base_url: &str,
+        model: &str,
+        prompt: &str,
+        text: &str,
+    ) -> Result<()> {
+        write_cache_file(&self.enhanced_text_path(base_url, model, prompt), text).await
+    }
+
+    fn enhanced_text_path(&self, base_url: &str, model: &str, prompt: &str) -> PathBuf {
+        // OpenAI is left out of the key, so existing cache entries still match
+        let key = if base_url == OPENAI_BASE_URL {
+            sha256::digest(format!("{}\n{}", model, prompt))
+        } else {
+            sha256::digest(format!("{}\n{}\n{}", base_url, model, prompt))
+        };
+        self.config
+            .cache_dir
+            .join(ENHANCED_DIR_NAME)
+            .join(format!("{}.txt", key))
+    }
+
+    /// Take the lock on a cache directory, waiting while another run holds it
+    pub async fn lock(&self, job_dir: &Path) -> Result<CacheLock> {
+        fs::create_dir_all(job_dir)
+            .await
+            .with_context(|| format!("Failed to create cache directory {:?}", job_dir))?;
+
+        let lock_path = job_dir.join(LOCK_FILE);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file {:?}", lock_path))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                println!("Waiting for another murmur run on the same file to finish...");
+                let file = tokio::task::spawn_blocking(move || file.lock().map(|()| file))
+                    .await?
+                    .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+                return Ok(CacheLock { _file: file });
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {:?}", lock_path));
             }
         }
 
-        Ok(())
+        Ok(CacheLock { _file: file })
+    }
+
+    /// Manifest of an earlier job on this file. Chunks it planned but never
+    /// got to cut are extracted again when the job continues.
+    pub async fn resumable_job(
+        &self,
+        job_dir: &Path,
+        file_hash: &str,
+    ) -> Result<Option<FileMetadata>> {
+        let manifest = self
+            .read_existing_metadata(&self.get_metadata_path(job_dir))
+            .await?;
+
+        Ok(manifest
+            .filter(|manifest| manifest.original_hash == file_hash && !manifest.chunks.is_empty()))
     }
 
-    /// Create metadata file for current processing session
+    /// Create the manifest for a freshly split job
     pub async fn create_metadata_file(
         &self,
+        job_dir: &Path,
         file_path: &Path,
-        file_size: u64,
         file_hash: &str,
-        chunk_count: usize,
-    ) -> Result<()> {
+        chunks: &[AudioChunk],
+        request: &TranscriptionRequest,
+    ) -> Result<FileMetadata> {
         let filename = utils::get_filename_or_default(file_path, "unknown_file");
 
         let metadata = FileMetadata {
             original_filename: filename,
-            original_size: file_size,
+            original_size: utils::get_file_size(file_path).await?,
             original_hash: file_hash.to_string(),
-            chunk_count,
+            chunk_count: chunks.len(),
             creation_time: utils::current_timestamp(),
+            language: request.language.clone(),
+            format: request.format,
+            chunks: chunks
+                .iter()
+                .map(|chunk| ChunkRecord {
+                    path: chunk.path.clone(),
+                    start: chunk.start,
+                    end: chunk.end,
+                    status: ChunkStatus::Pending,
+                    attempts: 0,
+                    last_error: None,
+                })
+                .collect(),
         };
 
-        let metadata_path = self.get_metadata_path();
-        let metadata_json = serde_json::to_string_pretty(&metadata)?;
+        self.save_metadata(job_dir, &metadata).await?;
+        Ok(metadata)
+    }
 
-        // Ensure directory exists
-        if let Some(parent) = Path::new(&metadata_path).parent() {
-            fs::create_dir_all(parent).await?;
+    /// Track chunk progress in a job's manifest
+    pub fn open_manifest(&self, job_dir: &Path, metadata: FileMetadata) -> JobManifest {
+        JobManifest {
+            path: self.get_metadata_path(job_dir),
+            metadata: tokio::sync::Mutex::new(metadata),
         }
+    }
+
+    async fn save_metadata(&self, job_dir: &Path, metadata: &FileMetadata) -> Result<()> {
+        let metadata_json = serde_json::to_string_pretty(metadata)?;
+        write_cache_file(&self.get_metadata_path(job_dir), &metadata_json).await
+    }
+
+    /// Get cached transcript for a chunk if one was made with the same parameters
+    pub async fn get_cached_transcript(
+        &self,
+        chunk_path: &str,
+        params_key: &str,
+    ) -> Result<Option<Transcript>> {
+        let cache_path = Self::transcript_cache_path(chunk_path, params_key);
+        Ok(read_transcript(Path::new(&cache_path)).await)
+    }
+
+    /// Save transcript to cache file
+    pub async fn save_transcript_cache(
+        &self,
+        chunk_path: &str,
+        params_key: &str,
+        transcript: &Transcript,
+    ) -> Result<()> {
+        let cache_path = Self::transcript_cache_path(chunk_path, params_key);
+        write_transcript(Path::new(&cache_path), transcript).await
+    }
 
-        fs::write(&metadata_path, metadata_json).await?;
+    /// Remove everything in a cache directory except its lock file.
+    ///
+    /// The lock file stays so that a run waiting on it still locks the same
+    /// file once this one is done.
+    pub async fn cleanup_job(&self, job_dir: &Path) -> Result<()> {
+        if let Ok(mut dir) = fs::read_dir(job_dir).await {
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                if entry.file_name() != LOCK_FILE {
+                    // Ignore errors - the file might already be removed
+                    fs::remove_file(entry.path()).await.ok();
+                }
+            }
+        }
 
         Ok(())
     }
 
-    /// Get cached transcript for a chunk if it exists
-    pub async fn get_cached_transcript(&self, chunk_path: &str) -> Result<Option<String>> {
-        let cache_path = format!("{}.transcript.txt", chunk_path);
+    /// Run a `murmur cache` action, printing its results
+    pub fn run_command(&self, command: &CacheCommand) -> Result<()> {
+        match command {
+            CacheCommand::List => self.print_list(),
+            CacheCommand::Stats => self.print_stats(),
+            CacheCommand::Prune {
+                older_than,
+                max_size,
+            } => {
+                let summary = self.prune(*older_than, *max_size)?;
+                print_summary(&summary);
+                Ok(())
+            }
+            CacheCommand::Clear => {
+                let summary = self.clear()?;
+                print_summary(&summary);
+                Ok(())
+            }
+        }
+    }
 
-        if !Path::new(&cache_path).exists() {
-            return Ok(None);
+    fn print_list(&self) -> Result<()> {
+        let entries = self.list_entries()?;
+        if entries.is_empty() {
+            println!("Cache is empty ({})", self.config.cache_dir.display());
+            return Ok(());
         }
 
-        match fs::read_to_string(&cache_path).await {
-            Ok(cached_text) if !cached_text.trim().is_empty() => Ok(Some(cached_text)),
-            _ => Ok(None),
+        let now = SystemTime::now();
+        println!(
+            "{:>6}  {:>9}  {:>6}  {:<10}  FILE",
+            "AGE", "SIZE", "CHUNKS", "KIND"
+        );
+        for entry in entries {
+            let age = now.duration_since(entry.modified).unwrap_or_default();
+            println!(
+                "{:>6}  {:>9}  {:>6}  {:<10}  {}",
+                format_age(age),
+                format_size(entry.size),
+                entry
+                    .chunk_count
+                    .map_or("-".to_string(), |count| count.to_string()),
+                entry.kind.label(),
+                entry.name.as_deref().unwrap_or("-")
+            );
         }
+        Ok(())
     }
 
-    /// Save transcript to cache file
-    pub async fn save_transcript_cache(&self, chunk_path: &str, text: &str) -> Result<()> {
-        let cache_path = format!("{}.transcript.txt", chunk_path);
+    fn print_stats(&self) -> Result<()> {
+        let entries = self.list_entries()?;
 
-        fs::write(&cache_path, text)
-            .await
-            .with_context(|| format!("Failed to save transcript cache to {}", cache_path))?;
+        println!("Cache directory: {}", self.config.cache_dir.display());
+        for (kind, title) in [
+            (CacheEntryKind::Transcript, "Transcripts"),
+            (CacheEntryKind::Chunks, "Unfinished files"),
+            (CacheEntryKind::Enhancement, "Enhancements"),
+        ] {
+            let matching: Vec<&CacheEntry> = entries.iter().filter(|e| e.kind == kind).collect();
+            let size: u64 = matching.iter().map(|e| e.size).sum();
+            println!(
+                "{:<17} {:>5}  {:>9}",
+                title,
+                matching.len(),
+                format_size(size)
+            );
+        }
 
+        let total: u64 = entries.iter().map(|e| e.size).sum();
+        println!(
+            "{:<17} {:>5}  {:>9}",
+            "Total",
+            entries.len(),
+            format_size(total)
+        );
+
+        if let Some(oldest) = entries.first() {
+            let age = SystemTime::now()
+                .duration_since(oldest.modified)
+                .unwrap_or_default();
+            println!("Oldest entry:     {}", format_age(age));
+        }
         Ok(())
     }
 
-    /// Clean up all temporary files after successful processing
-    pub async fn cleanup_temp_files(&self) -> Result<()> {
-        let segment_dir = self.config.temp_dir_path().to_string_lossy().to_string();
+    /// Everything in the cache, oldest first
+    pub fn list_entries(&self) -> Result<Vec<CacheEntry>> {
+        let root = &self.config.cache_dir;
+        let mut entries = Vec::new();
 
-        if let Ok(mut dir) = fs::read_dir(&segment_dir).await {
-            while let Ok(Some(entry)) = dir.next_entry().await {
-                fs::remove_file(entry.path()).await.ok();
+        for path in dir_paths(&root.join(CHUNKS_DIR_NAME))? {
+            if !path.is_dir() {
+                continue;
             }
+            let metadata: Option<FileMetadata> =
+                read_cache_file_sync(&self.get_metadata_path(&path))
+                    .and_then(|content| serde_json::from_str(&content).ok());
+            let (size, modified) = disk_usage(&path)?;
+            entries.push(CacheEntry {
+                kind: CacheEntryKind::Chunks,
+                name: metadata.as_ref().map(|m| m.original_filename.clone()),
+                chunk_count: metadata.map(|m| m.chunk_count),
+                size,
+                modified,
+                path,
+            });
         }
 
-        // Try to remove the directory itself
-        fs::remove_dir(&segment_dir).await.ok();
+        for path in dir_paths(&root.join(TRANSCRIPTS_DIR_NAME))? {
+            let entry: Option<FileTranscriptEntry> =
+                read_cache_file_sync(&path).and_then(|content| serde_json::from_str(&content).ok());
+            let (size, modified) = disk_usage(&path)?;
+            entries.push(CacheEntry {
+                kind: CacheEntryKind::Transcript,
+                name: entry.map(|e| e.original_filename),
+                chunk_count: None,
+                size,
+                modified,
+                path,
+            });
+        }
 
-        Ok(())
+        for path in dir_paths(&root.join(ENHANCED_DIR_NAME))? {
+            let (size, modified) = disk_usage(&path)?;
+            entries.push(CacheEntry {
+                kind: CacheEntryKind::Enhancement,
+                name: None,
+                chunk_count: None,
+                size,
+                modified,
+                path,
+            });
+        }
+
+        entries.sort_by_key(|entry| entry.modified);
+        Ok(entries)
     }
 
-    /// Clean up all cached files (used when hash mismatch is detected)
-    async fn cleanup_all_cached_files(&self) -> Result<()> {
-        let segment_dir = self.config.temp_dir_path().to_string_lossy().to_string();
+    /// Remove entries older than `older_than`, then the oldest remaining ones
+    /// until the cache is no larger than `max_size` bytes
+    pub fn prune(
+        &self,
+        older_than: Option<Duration>,
+        max_size: Option<u64>,
+    ) -> Result<PruneSummary> {
+        let entries = self.list_entries()?;
+        let now = SystemTime::now();
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut summary = PruneSummary::default();
 
-        if !Path::new(&segment_dir).exists() {
-            return Ok(());
+        for entry in entries {
+            let age = now.duration_since(entry.modified).unwrap_or_default();
+            let too_old = older_than.is_some_and(|limit| age > limit);
+            let too_big = max_size.is_some_and(|limit| total > limit);
+            if !too_old && !too_big {
+                continue;
+            }
+
+            if self.remove_entry(&entry)? {
+                summary.removed += 1;
+                summary.freed += entry.size;
+                total -= entry.size;
+            } else {
+                summary.in_use += 1;
+            }
         }
 
-        match fs::read_dir(&segment_dir).await {
-            Ok(mut dir) => {
-                while let Ok(Some(entry)) = dir.next_entry().await {
-                    if let Err(_e) = fs::remove_file(entry.path()).await {
-                        // Ignore error - file might already be removed
-                    }
-                }
+        Ok(summary)
+    }
 
-                // Try to remove the directory itself
-                if let Err(_e) = fs::remove_dir(&segment_dir).await {
-                    // Ignore error - directory might not be empty or already removed
-                }
+    /// Remove every entry that isn't in use by a running murmur
+    pub fn clear(&self) -> Result<PruneSummary> {
+        let mut summary = PruneSummary::default();
+        for entry in self.list_entries()? {
+            if self.remove_entry(&entry)? {
+                summary.removed += 1;
+                summary.freed += entry.size;
+            } else {
+                summary.in_use += 1;
             }
-            Err(_e) => {
-                // Ignore error - directory might not exist
+        }
+        Ok(summary)
+    }
+
+    /// Remove an entry, returning false if it is locked by a running murmur
+    fn remove_entry(&self, entry: &CacheEntry) -> Result<bool> {
+        if entry.kind != CacheEntryKind::Chunks {
+            std::fs::remove_file(&entry.path)
+                .with_context(|| format!("Failed to remove {:?}", entry.path))?;
+            return Ok(true);
+        }
+
+        // Hold the lock while deleting so a run can't start in the directory meanwhile
+        let lock_file = std::fs::File::open(entry.path.join(LOCK_FILE)).ok();
+        if let Some(file) = &lock_file {
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Ok(false),
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", entry.path))
+                }
             }
         }
 
-        Ok(())
+        std::fs::remove_dir_all(&entry.path)
+            .with_context(|| format!("Failed to remove {:?}", entry.path))?;
+        Ok(true)
+    }
+
+    fn transcript_cache_path(chunk_path: &str, params_key: &str) -> String {
+        format!("{}.{}.transcript.json", chunk_path, params_key)
+    }
+
+    fn get_metadata_path(&self, job_dir: &Path) -> PathBuf {
+        job_dir.join(&self.config.metadata_file)
+    }
+
+    /// The manifest at `metadata_path`, if there is one. A damaged manifest is
+    /// removed, so the job starts over on the next run.
+    async fn read_existing_metadata(&self, metadata_path: &Path) -> Result<Option<FileMetadata>> {
+        if !metadata_path.exists() {
+            return Ok(None);
+        }
+
+        let metadata = read_cache_file(metadata_path)
+            .await
+            .and_then(|json| serde_json::from_str(&json).ok());
+        if metadata.is_none() {
+            fs::remove_file(metadata_path).await.ok();
+        }
+        metadata.map(Some).with_context(|| {
+            MurmurError::CacheCorrupt(format!(
+                "Job manifest {:?} was damaged and has been removed",
+                metadata_path
+            ))
+        })
+    }
+}
+
+/// A job's manifest, written again after every chunk so progress survives
+/// crashes and reboots
+pub struct JobManifest {
+    path: PathBuf,
+    metadata: tokio::sync::Mutex<FileMetadata>,
+}
+
+impl JobManifest {
+    /// The job's chunks with their current status
+    pub async fn chunks(&self) -> Vec<ChunkRecord> {
+        self.metadata.lock().await.chunks.clone()
+    }
+
+    /// Record a finished chunk
+    pub async fn record_success(&self, chunk_index: usize) -> Result<()> {
+        self.update(chunk_index, ChunkStatus::Done, None).await
     }
 
-    fn get_metadata_path(&self) -> String {
-        format!(
-            "{}/{}",
-            self.config.temp_dir_path().to_string_lossy(),
-            self.config.metadata_file
+    /// Record a chunk that failed, to be retried by the next run
+    pub async fn record_failure(&self, chunk_index: usize, error: &anyhow::Error) -> Result<()> {
+        self.update(
+            chunk_index,
+            ChunkStatus::Failed,
+            Some(format!("{:#}", error)),
         )
+        .await
     }
 
-    async fn read_existing_metadata(&self, metadata_path: &str) -> Result<FileMetadata> {
-        let metadata_json = fs::read_to_string(metadata_path).await?;
-        serde_json::from_str(&metadata_json).map_err(Into::into)
+    async fn update(
+        &self,
+        chunk_index: usize,
+        status: ChunkStatus,
+        error: Option<String>,
+    ) -> Result<()> {
+        // The lock is held while writing so updates land in order
+        let mut metadata = self.metadata.lock().await;
+        if let Some(chunk) = metadata.chunks.get_mut(chunk_index) {
+            chunk.status = status;
+            chunk.attempts += 1;
+            chunk.last_error = error;
+        }
+        let metadata_json = serde_json::to_string_pretty(&*metadata)?;
+        write_cache_file(&self.path, &metadata_json).await
     }
 }
 
+/// Transcript of a whole input file, with enough about the file to list it
+#[derive(Serialize, Deserialize, Debug)]
+struct FileTranscriptEntry {
+    original_filename: String,
+    original_size: u64,
+    transcript: Transcript,
+}
+
+/// Read a cached transcript, treating unreadable or empty entries as missing
+async fn read_transcript(path: &Path) -> Option<Transcript> {
+    let content = read_cache_file(path).await?;
+    serde_json::from_str::<Transcript>(&content)
+        .ok()
+        .filter(|transcript| !transcript.text.trim().is_empty())
+}
+
+async fn write_transcript(path: &Path, transcript: &Transcript) -> Result<()> {
+    write_cache_file(path, &serde_json::to_string(transcript)?).await
+}
+
+/// Write a cache file atomically, behind a header line holding the SHA-256 of
+/// its content
+async fn write_cache_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let sealed = format!(
+        "{}{}\n{}",
+        CHECKSUM_PREFIX,
+        sha256::digest(content),
+        content
+    );
+    utils::write_atomic(path, sealed)
+        .await
+        .context("Failed to save cache entry")
+}
+
+/// Read a cache file, deleting it if it is damaged or was cut short
+async fn read_cache_file(path: &Path) -> Option<String> {
+    let raw = fs::read_to_string(path).await.ok();
+    match raw.as_deref().and_then(verify_checksum) {
+        Some(content) => Some(content.to_string()),
+        None => {
+            if raw.is_some() {
+                fs::remove_file(path).await.ok();
+            }
+            None
+        }
+    }
+}
+
+/// Read a cache file for listing, leaving damaged files in place
+fn read_cache_file_sync(path: &Path) -> Option<String> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    verify_checksum(&raw).map(str::to_string)
+}
+
+/// Content of a cache file if its checksum header matches
+fn verify_checksum(raw: &str) -> Option<&str> {
+    let (header, content) = raw.split_once('\n')?;
+    let checksum = header.strip_prefix(CHECKSUM_PREFIX)?;
+    (sha256::digest(content) == checksum).then_some(content)
+}
+
+/// Paths of the entries in a directory; a missing directory has none
+fn dir_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read cache directory {:?}", dir)),
+    }
+}
+
+/// Total size and latest modification time of a file or directory tree
+fn disk_usage(path: &Path) -> Result<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let mut size = metadata.len();
+    let mut modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+    if metadata.is_dir() {
+        size = 0;
+        for child in dir_paths(path)? {
+            let (child_size, child_modified) = disk_usage(&child)?;
+            size += child_size;
+            modified = modified.max(child_modified);
+        }
+    }
+
+    Ok((size, modified))
+}
+
+fn print_summary(summary: &PruneSummary) {
+    println!(
+        "Removed {} cache entries, freed {}",
+        summary.removed,
+        format_size(summary.freed)
+    );
+    if summary.in_use > 0 {
+        println!("Skipped {} in use by a running murmur", summary.in_use);
+    }
+}
+
+/// Parse an age like `90s`, `45m`, `12h`, `30d` or `2w`
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("Missing unit in '{}' (use s, m, h, d or w)", value))?;
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid age '{}'", value))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit '{}' (use s, m, h, d or w)", unit)),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Parse a size like `1048576`, `500K`, `500M` or `1.5G` into bytes
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}'", value))?;
+
+    let multiplier: u64 = match unit.to_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown unit '{}' (use K, M or G)", unit)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", utils::bytes_to_mb(bytes))
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+/// Exclusive lock on a cache directory, released when dropped
+pub struct CacheLock {
+    _file: std::fs::File,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    fn test_config(temp_dir: &TempDir) -> Config {
+        Config {
+            cache_dir: temp_dir.path().join("cache"),
+            ..Config::default()
+        }
+    }
+
+    /// Write `count` chunk files of `size` bytes into `job_dir`
+    async fn write_test_chunks(job_dir: &Path, count: usize, size: usize) -> Vec<AudioChunk> {
+        fs::create_dir_all(job_dir).await.unwrap();
+        let mut chunks = Vec::new();
+        for i in 0..count {
+            let path = job_dir.join(format!("chunk_{:03}.mp3", i));
+            fs::write(&path, vec![0u8; size]).await.unwrap();
+            chunks.push(AudioChunk {
+                path: path.to_string_lossy().to_string(),
+                start: i as f64 * 60.0,
+                end: (i + 1) as f64 * 60.0,
+            });
+        }
+        chunks
+    }
+
     #[tokio::test]
     async fn test_cache_manager_creation() {
         let config = Config::default();
         let cache_manager = CacheManager::new(&config);
 
-        assert_eq!(cache_manager.config.temp_dir_name, config.temp_dir_name);
+        assert_eq!(cache_manager.config.cache_dir, config.cache_dir);
         assert_eq!(cache_manager.config.metadata_file, config.metadata_file);
     }
 
+    #[test]
+    fn test_job_dir_depends_on_file_and_chunking_settings() {
+        let config = Config::default();
+        let cache_manager = CacheManager::new(&config);
+        let job_dir = cache_manager.job_dir("hash_a");
+
+        assert!(job_dir.starts_with(config.cache_dir.join(CHUNKS_DIR_NAME)));
+        assert_eq!(job_dir, cache_manager.job_dir("hash_a"));
+        assert_ne!(job_dir, cache_manager.job_dir("hash_b"));
+
+        let opus = CacheManager::new(&Config {
+            preprocess: crate::media::Preprocess::Opus,
+            ..Config::default()
+        });
+        assert_ne!(job_dir, opus.job_dir("hash_a"));
+    }
+
+    #[tokio::test]
+    async fn test_lock_is_exclusive() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let job_dir = cache_manager.job_dir("hash");
+
+        let lock = cache_manager.lock(&job_dir).await.unwrap();
+        assert!(job_dir.join(LOCK_FILE).exists());
+
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            cache_manager.lock(&job_dir),
+        )
+        .await;
+        assert!(waiting.is_err(), "Second lock should wait for the first");
+
+        drop(lock);
+        assert!(cache_manager.lock(&job_dir).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_get_cached_transcript_nonexistent() {
         let config = Config::default();
         let cache_manager = CacheManager::new(&config);
 
         let result = cache_manager
-            .get_cached_transcript("/nonexistent/chunk.mp3")
+            .get_cached_transcript("/nonexistent/chunk.mp3", "key")
             .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), None);
@@ -189,83 +941,493 @@ mod tests {
         let config = Config::default();
         let cache_manager = CacheManager::new(&config);
 
-        let test_content = "Test transcript content";
+        let test_content = Transcript::from_text("Test transcript content");
 
         // Save transcript
         let save_result = cache_manager
-            .save_transcript_cache(&chunk_path, test_content)
+            .save_transcript_cache(&chunk_path, "key", &test_content)
             .await;
         assert!(save_result.is_ok());
 
         // Get cached transcript
-        let get_result = cache_manager.get_cached_transcript(&chunk_path).await;
+        let get_result = cache_manager
+            .get_cached_transcript(&chunk_path, "key")
+            .await;
         assert!(get_result.is_ok());
-        assert_eq!(get_result.unwrap(), Some(test_content.to_string()));
+        assert_eq!(get_result.unwrap(), Some(test_content));
+
+        // Other parameters miss
+        let get_result = cache_manager
+            .get_cached_transcript(&chunk_path, "other_key")
+            .await;
+        assert_eq!(get_result.unwrap(), None);
+    }
+
+    #[test]
+    fn test_params_key_changes_with_transcription_settings() {
+        let config = Config::default();
+        let request = TranscriptionRequest::microphone().language("en").build();
+        let key = TranscriptionParams::new(&config, &request).cache_key();
+        assert_eq!(key, TranscriptionParams::new(&config, &request).cache_key());
+
+        let zh = TranscriptionRequest::microphone().language("zh").build();
+        assert_ne!(key, TranscriptionParams::new(&config, &zh).cache_key());
+
+        let srt = TranscriptionRequest {
+            format: crate::OutputFormat::Srt,
+            ..request.clone()
+        };
+        assert_ne!(key, TranscriptionParams::new(&config, &srt).cache_key());
+
+        let other_model = Config {
+            transcription_model: "whisper-large-v3".to_string(),
+            ..Config::default()
+        };
+        assert_ne!(
+            key,
+            TranscriptionParams::new(&other_model, &request).cache_key()
+        );
+
+        // A model picked for the request counts like one from the config
+        let override_model = TranscriptionRequest {
+            model: Some("whisper-large-v3".to_string()),
+            ..request.clone()
+        };
+        assert_eq!(
+            TranscriptionParams::new(&config, &override_model).cache_key(),
+            TranscriptionParams::new(&other_model, &request).cache_key()
+        );
+
+        let local = Config {
+            backend: BackendKind::WhisperCpp,
+            ..Config::default()
+        };
+        assert_ne!(key, TranscriptionParams::new(&local, &request).cache_key());
+
+        let prompted = TranscriptionRequest {
+            prompt: Some("Kubernetes, etcd".to_string()),
+            ..request.clone()
+        };
+        assert_ne!(
+            key,
+            TranscriptionParams::new(&config, &prompted).cache_key()
+        );
+
+        let warmer = TranscriptionRequest {
+            temperature: Some(0.4),
+            ..request.clone()
+        };
+        assert_ne!(key, TranscriptionParams::new(&config, &warmer).cache_key());
+
+        // Keys from before chaining existed stay valid while it is off
+        let params = serde_json::to_string(&TranscriptionParams::new(&config, &request)).unwrap();
+        assert!(!params.contains("chain_chunks"));
+        let chained = Config {
+            chain_chunks: true,
+            ..Config::default()
+        };
+        assert_ne!(
+            key,
+            TranscriptionParams::new(&chained, &request).cache_key()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_transcript_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let transcript = Transcript::from_text("Whole file");
+        let file_path = temp_dir.path().join("talk.mp3");
+        fs::write(&file_path, "audio").await.unwrap();
+
+        assert_eq!(
+            cache_manager
+                .get_file_transcript("hash", "key")
+                .await
+                .unwrap(),
+            None
+        );
+        cache_manager
+            .save_file_transcript(&file_path, "hash", "key", &transcript)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cache_manager
+                .get_file_transcript("hash", "key")
+                .await
+                .unwrap(),
+            Some(transcript)
+        );
+        assert_eq!(
+            cache_manager
+                .get_file_transcript("hash", "other_key")
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            cache_manager
+                .get_file_transcript("other_hash", "key")
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enhanced_text_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+
+        assert_eq!(
+            cache_manager
+                .get_enhanced_text(OPENAI_BASE_URL, "gpt", "prompt")
+                .await,
+            None
+        );
+        cache_manager
+            .save_enhanced_text(OPENAI_BASE_URL, "gpt", "prompt", "Enhanced.")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cache_manager
+                .get_enhanced_text(OPENAI_BASE_URL, "gpt", "prompt")
+                .await,
+            Some("Enhanced.".to_string())
+        );
+        assert_eq!(
+            cache_manager
+                .get_enhanced_text(OPENAI_BASE_URL, "other", "prompt")
+                .await,
+            None
+        );
+        // Another server may answer differently with the same model name
+        assert_eq!(
+            cache_manager
+                .get_enhanced_text("http://localhost:8080/v1", "gpt", "prompt")
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_damaged_entries_are_discarded() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_path = temp_dir
+            .path()
+            .join("chunk_000.mp3")
+            .to_string_lossy()
+            .to_string();
+        let cache_manager = CacheManager::new(&Config::default());
+        let transcript = Transcript::from_text("A complete transcript of the chunk");
+
+        cache_manager
+            .save_transcript_cache(&chunk_path, "key", &transcript)
+            .await
+            .unwrap();
+        let cache_path = CacheManager::transcript_cache_path(&chunk_path, "key");
+        let written = fs::read_to_string(&cache_path).await.unwrap();
+        assert!(written.starts_with(CHECKSUM_PREFIX));
+
+        // Simulate a write cut short by a crash
+        fs::write(&cache_path, &written[..written.len() - 5])
+            .await
+            .unwrap();
+        assert_eq!(
+            cache_manager
+                .get_cached_transcript(&chunk_path, "key")
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(!Path::new(&cache_path).exists());
+
+        // Entries without a checksum header are not trusted either
+        fs::write(&cache_path, serde_json::to_string(&transcript).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            cache_manager
+                .get_cached_transcript(&chunk_path, "key")
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("2048"), Ok(2048));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("20mb"), Ok(20 * 1024 * 1024));
+        assert!(parse_size("big").is_err());
+        assert!(parse_size("5X").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_prune_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+
+        let file_path = temp_dir.path().join("talk.mp3");
+        fs::write(&file_path, "audio").await.unwrap();
+        cache_manager
+            .save_file_transcript(&file_path, "hash", "key", &Transcript::from_text("Hi"))
+            .await
+            .unwrap();
+        cache_manager
+            .save_enhanced_text(OPENAI_BASE_URL, "gpt", "prompt", "Enhanced.")
+            .await
+            .unwrap();
+
+        let long_path = temp_dir.path().join("long.wav");
+        fs::write(&long_path, vec![0u8; 1024]).await.unwrap();
+        let job_dir = cache_manager.job_dir("big_hash");
+        let chunks = write_test_chunks(&job_dir, 3, 4096).await;
+        cache_manager
+            .create_metadata_file(
+                &job_dir,
+                &long_path,
+                "big_hash",
+                &chunks,
+                &TranscriptionRequest::microphone().build(),
+            )
+            .await
+            .unwrap();
+
+        let entries = cache_manager.list_entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        let chunks = entries
+            .iter()
+            .find(|e| e.kind == CacheEntryKind::Chunks)
+            .unwrap();
+        assert_eq!(chunks.name.as_deref(), Some("long.wav"));
+        assert_eq!(chunks.chunk_count, Some(3));
+        assert!(chunks.size >= 4096);
+        let transcript = entries
+            .iter()
+            .find(|e| e.kind == CacheEntryKind::Transcript)
+            .unwrap();
+        assert_eq!(transcript.name.as_deref(), Some("talk.mp3"));
+
+        // Nothing is old enough yet
+        let summary = cache_manager
+            .prune(Some(Duration::from_secs(3600)), None)
+            .unwrap();
+        assert_eq!(summary.removed, 0);
+
+        // Shrinking below the chunk directory's size removes it along with older entries
+        let summary = cache_manager.prune(None, Some(1024)).unwrap();
+        assert!(summary.removed >= 1);
+        assert!(!job_dir.exists());
+        let remaining: u64 = cache_manager
+            .list_entries()
+            .unwrap()
+            .iter()
+            .map(|e| e.size)
+            .sum();
+        assert!(remaining <= 1024);
+    }
+
+    #[tokio::test]
+    async fn test_clear_skips_locked_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+
+        let busy_dir = cache_manager.job_dir("busy");
+        let idle_dir = cache_manager.job_dir("idle");
+        let _lock = cache_manager.lock(&busy_dir).await.unwrap();
+        drop(cache_manager.lock(&idle_dir).await.unwrap());
+
+        let summary = cache_manager.clear().unwrap();
+        assert_eq!(summary.removed, 1);
+        assert_eq!(summary.in_use, 1);
+        assert!(busy_dir.exists());
+        assert!(!idle_dir.exists());
     }
 
     #[tokio::test]
     async fn test_create_metadata_file() {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("test.mp3");
+        fs::write(&file_path, vec![0u8; 1024]).await.unwrap();
 
-        // Create a custom config with temp directory in our test directory
-        let mut config = Config::default();
-        config.temp_dir_name = temp_dir.path().join("cache").to_string_lossy().to_string();
-
-        let cache_manager = CacheManager::new(&config);
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let job_dir = cache_manager.job_dir("test_hash");
+        let chunks = write_test_chunks(&job_dir, 5, 16).await;
+        let request = TranscriptionRequest::file(&file_path)
+            .language("de")
+            .format(crate::OutputFormat::Srt)
+            .build();
 
         let result = cache_manager
-            .create_metadata_file(&file_path, 1024, "test_hash", 5)
+            .create_metadata_file(&job_dir, &file_path, "test_hash", &chunks, &request)
             .await;
 
         assert!(result.is_ok());
 
         // Verify metadata file was created
-        let metadata_path = cache_manager.get_metadata_path();
-        assert!(Path::new(&metadata_path).exists());
+        let metadata_path = cache_manager.get_metadata_path(&job_dir);
+        assert!(metadata_path.exists());
 
         // Verify content
-        let metadata_content = fs::read_to_string(&metadata_path).await.unwrap();
+        let metadata_content = read_cache_file(&metadata_path).await.unwrap();
         let metadata: FileMetadata = serde_json::from_str(&metadata_content).unwrap();
 
         assert_eq!(metadata.original_filename, "test.mp3");
         assert_eq!(metadata.original_size, 1024);
         assert_eq!(metadata.original_hash, "test_hash");
         assert_eq!(metadata.chunk_count, 5);
+        assert_eq!(metadata.language.as_deref(), Some("de"));
+        assert_eq!(metadata.format, crate::OutputFormat::Srt);
+        assert_eq!(metadata.chunks[1].path, chunks[1].path);
+        assert_eq!(metadata.chunks[1].start, 60.0);
+        assert!(metadata
+            .chunks
+            .iter()
+            .all(|chunk| chunk.status == ChunkStatus::Pending && chunk.attempts == 0));
     }
 
     #[tokio::test]
-    async fn test_cleanup_all_cached_files() {
+    async fn test_manifest_tracks_progress_for_resume() {
         let temp_dir = TempDir::new().unwrap();
-        let cache_dir = temp_dir.path().join("test_cache");
-        fs::create_dir_all(&cache_dir).await.unwrap();
+        let file_path = temp_dir.path().join("long.wav");
+        fs::write(&file_path, "audio").await.unwrap();
+
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let job_dir = cache_manager.job_dir("hash");
+        assert!(cache_manager
+            .resumable_job(&job_dir, "hash")
+            .await
+            .unwrap()
+            .is_none());
+
+        let chunks = write_test_chunks(&job_dir, 3, 16).await;
+        let metadata = cache_manager
+            .create_metadata_file(
+                &job_dir,
+                &file_path,
+                "hash",
+                &chunks,
+                &TranscriptionRequest::microphone().build(),
+            )
+            .await
+            .unwrap();
+
+        let manifest = cache_manager.open_manifest(&job_dir, metadata);
+        manifest.record_success(0).await.unwrap();
+        manifest
+            .record_failure(1, &anyhow::anyhow!("rate limited"))
+            .await
+            .unwrap();
+        manifest
+            .record_failure(1, &anyhow::anyhow!("server error"))
+            .await
+            .unwrap();
+
+        // A later run sees the progress on disk
+        let resumed = cache_manager
+            .resumable_job(&job_dir, "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.completed_chunks(), 1);
+        assert_eq!(resumed.chunks[0].status, ChunkStatus::Done);
+        assert_eq!(resumed.chunks[1].status, ChunkStatus::Failed);
+        assert_eq!(resumed.chunks[1].attempts, 2);
+        assert_eq!(
+            resumed.chunks[1].last_error.as_deref(),
+            Some("server error")
+        );
+        assert_eq!(resumed.chunks[2].status, ChunkStatus::Pending);
+
+        // Chunks that were never cut don't stop a resume, another file does
+        fs::remove_file(&chunks[2].path).await.unwrap();
+        assert!(cache_manager
+            .resumable_job(&job_dir, "hash")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(cache_manager
+            .resumable_job(&job_dir, "other")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_damaged_manifest_is_reported_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let job_dir = cache_manager.job_dir("hash");
+        fs::create_dir_all(&job_dir).await.unwrap();
+        let metadata_path = cache_manager.get_metadata_path(&job_dir);
+        fs::write(&metadata_path, "sha256:0000\n{\"truncated\"")
+            .await
+            .unwrap();
+
+        let error = cache_manager
+            .resumable_job(&job_dir, "hash")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::CacheCorrupt(_))
+        ));
+
+        // The damaged manifest is gone, so the next run starts over
+        assert!(!metadata_path.exists());
+        assert!(cache_manager
+            .resumable_job(&job_dir, "hash")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_job() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_manager = CacheManager::new(&test_config(&temp_dir));
+        let job_dir = cache_manager.job_dir("hash");
+        let other_dir = cache_manager.job_dir("other_hash");
+        fs::create_dir_all(&other_dir).await.unwrap();
+        fs::write(other_dir.join("chunk_000.mp3"), "other run")
+            .await
+            .unwrap();
+
+        let _lock = cache_manager.lock(&job_dir).await.unwrap();
 
         // Create some test files
         let test_files = vec![
             "chunk_001.mp3",
             "chunk_002.mp3",
-            "chunk_001.mp3.transcript.txt",
+            "chunk_001.mp3.transcript.json",
             "metadata.json",
         ];
         for file_name in &test_files {
-            let file_path = cache_dir.join(file_name);
+            let file_path = job_dir.join(file_name);
             fs::write(&file_path, "test content").await.unwrap();
             assert!(file_path.exists());
         }
 
-        // Create custom config
-        let mut config = Config::default();
-        config.temp_dir_name = cache_dir.to_string_lossy().to_string();
-
-        let cache_manager = CacheManager::new(&config);
-
-        // Call cleanup function
-        let result = cache_manager.cleanup_all_cached_files().await;
+        let result = cache_manager.cleanup_job(&job_dir).await;
         assert!(result.is_ok());
 
         // Verify files are deleted
         for file_name in &test_files {
-            let file_path = cache_dir.join(file_name);
+            let file_path = job_dir.join(file_name);
             assert!(
                 !file_path.exists(),
                 "File {} should be deleted",
@@ -273,7 +1435,8 @@ mod tests {
             );
         }
 
-        // Verify directory is also deleted
-        assert!(!cache_dir.exists(), "Directory should be deleted");
+        // The lock file and other runs' files are left alone
+        assert!(job_dir.join(LOCK_FILE).exists());
+        assert!(other_dir.join("chunk_000.mp3").exists());
     }
 }
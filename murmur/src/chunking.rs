@@ -1,8 +1,33 @@
 use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use std::path::Path;
-use std::process::Command;
+use tokio::process::Command;
+
+use crate::media::{self, AudioEngine};
+use crate::utils::{Config, EXTRACTION_JOBS};
+
+/// A piece of the input audio cut out for transcription
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioChunk {
+    pub path: String,
+    /// Start of the chunk in the original audio, in seconds
+    pub start: f64,
+    /// End of the chunk in the original audio, in seconds
+    pub end: f64,
+}
+
+/// A stretch of silence in the input audio, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Silence {
+    pub start: f64,
+    pub end: f64,
+}
 
-use crate::utils::{self, Config};
+impl Silence {
+    fn midpoint(&self) -> f64 {
+        (self.start + self.end) / 2.0
+    }
+}
 
 /// Audio file chunking functionality
 pub struct AudioChunker {
@@ -16,12 +41,61 @@ impl AudioChunker {
         }
     }
 
-    pub async fn split_audio_file(&self, input_path: &Path) -> Result<Vec<String>> {
-        let total_size = utils::get_file_size(input_path).await?;
-        let segment_dir = self.prepare_segment_directory().await?;
+    /// Cut the input into chunks written to `segment_dir`
+    pub async fn split_audio_file(
+        &self,
+        input_path: &Path,
+        segment_dir: &Path,
+    ) -> Result<Vec<AudioChunk>> {
+        let chunks = self.plan(input_path, segment_dir).await?;
+
+        stream::iter(&chunks)
+            .map(|chunk| self.extract_chunk(input_path, chunk))
+            .buffer_unordered(EXTRACTION_JOBS)
+            .try_collect::<()>()
+            .await?;
+
+        Ok(chunks)
+    }
+
+    /// Work out where the input will be cut, without extracting anything.
+    ///
+    /// Silences are only searched for around each cut, so planning a long
+    /// file takes seconds. Chunks are then cut with [`Self::extract_chunk`].
+    pub async fn plan(&self, input_path: &Path, segment_dir: &Path) -> Result<Vec<AudioChunk>> {
+        let engine = AudioEngine::detect();
+        tokio::fs::create_dir_all(segment_dir).await?;
+
+        let duration = self.get_audio_duration(input_path).await?;
+        // Chunks are re-encoded, so their size follows the output encoding and
+        // not the input: a low-bitrate voice memo makes much larger MP3 chunks
+        let total_size = self.encoded_size(engine, duration);
+        let chunk_info = self.calculate_chunk_parameters(total_size, duration);
+        let mut planner = ChunkPlanner::new(&self.config, duration, chunk_info.seconds_per_chunk);
+
+        let mut chunks = Vec::new();
+        loop {
+            let silences = match planner.search_window() {
+                Some((from, to)) if self.config.silence_detection => {
+                    self.detect_silences(input_path, from, to).await?
+                }
+                _ => Vec::new(),
+            };
+            let Some((start, end)) = planner.next_chunk(&silences) else {
+                break;
+            };
 
-        let duration = self.get_audio_duration(input_path)?;
-        let chunks = self.create_chunks(input_path, total_size, duration, &segment_dir)?;
+            chunks.push(AudioChunk {
+                path: format!(
+                    "{}/chunk_{:03}.{}",
+                    segment_dir.display(),
+                    chunks.len(),
+                    engine.extension(self.config.preprocess)
+                ),
+                start,
+                end,
+            });
+        }
 
         if chunks.is_empty() {
             anyhow::bail!("Failed to create any audio chunks");
@@ -30,17 +104,92 @@ impl AudioChunker {
         Ok(chunks)
     }
 
-    async fn prepare_segment_directory(&self) -> Result<String> {
-        let segment_dir = self.config.temp_dir_path().to_string_lossy().to_string();
+    /// Cut one planned chunk out of the input. The chunk only shows up at its
+    /// path once it has been written completely.
+    pub async fn extract_chunk(&self, input_path: &Path, chunk: &AudioChunk) -> Result<()> {
+        let chunk_path = Path::new(&chunk.path);
+        let extension = chunk_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let partial_path = chunk_path.with_extension(format!("part.{}", extension));
+
+        match AudioEngine::detect() {
+            AudioEngine::Ffmpeg => {
+                self.extract_with_ffmpeg(input_path, chunk, &partial_path)
+                    .await?
+            }
+            #[cfg(feature = "native-audio")]
+            AudioEngine::Native => {
+                let (input_path, output_path) = (input_path.to_owned(), partial_path.clone());
+                let (start, end) = (chunk.start, chunk.end);
+                let extracted = tokio::task::spawn_blocking(move || {
+                    crate::native_audio::extract(&input_path, &output_path, start, Some(end))
+                })
+                .await?;
+                if let Err(e) = extracted {
+                    let _ = tokio::fs::remove_file(&partial_path).await;
+                    return Err(e);
+                }
+            }
+        }
 
-        if !Path::new(&segment_dir).exists() {
-            std::fs::create_dir_all(&segment_dir)?;
+        tokio::fs::rename(&partial_path, chunk_path)
+            .await
+            .with_context(|| format!("Failed to write chunk {:?}", chunk_path))
+    }
+
+    async fn extract_with_ffmpeg(
+        &self,
+        input_path: &Path,
+        chunk: &AudioChunk,
+        partial_path: &Path,
+    ) -> Result<()> {
+        let output = Command::new("ffmpeg")
+            .args([
+                "-y", // Overwrite output files without asking
+                // Seeking before the input skips straight to the chunk
+                "-ss",
+                &chunk.start.to_string(),
+                "-t",
+                &(chunk.end - chunk.start).to_string(),
+                "-i",
+                input_path.to_str().context("Invalid file path encoding")?,
+                "-vn",
+            ])
+            .args(
+                self.config
+                    .preprocess
+                    .ffmpeg_codec_args(self.config.preprocess_bitrate_kbps),
+            )
+            .args([
+                "-loglevel",
+                "error",
+                partial_path
+                    .to_str()
+                    .context("Invalid file path encoding")?,
+            ])
+            .output()
+            .await
+            .map_err(media::spawn_error)?;
+
+        if !output.status.success() {
+            let _ = tokio::fs::remove_file(partial_path).await;
+            let error = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("FFmpeg error when creating chunk: {}", error);
         }
 
-        Ok(segment_dir)
+        Ok(())
     }
 
-    fn get_audio_duration(&self, input_path: &Path) -> Result<f64> {
+    async fn get_audio_duration(&self, input_path: &Path) -> Result<f64> {
+        #[cfg(feature = "native-audio")]
+        if AudioEngine::detect() == AudioEngine::Native {
+            let input_path = input_path.to_owned();
+            return tokio::task::spawn_blocking(move || crate::native_audio::duration(&input_path))
+                .await?;
+        }
+
         let duration_output = Command::new("ffprobe")
             .args([
                 "-v",
@@ -51,7 +200,9 @@ impl AudioChunker {
                 "default=noprint_wrappers=1:nokey=1",
                 input_path.to_str().context("Invalid file path encoding")?,
             ])
-            .output()?;
+            .output()
+            .await
+            .map_err(media::spawn_error)?;
 
         if !duration_output.status.success() {
             let error = String::from_utf8_lossy(&duration_output.stderr);
@@ -65,53 +216,65 @@ impl AudioChunker {
         duration_str.parse().map_err(Into::into)
     }
 
-    fn create_chunks(
-        &self,
-        input_path: &Path,
-        total_size: u64,
-        duration: f64,
-        segment_dir: &str,
-    ) -> Result<Vec<String>> {
-        let chunk_info = self.calculate_chunk_parameters(total_size, duration);
-
-        let mut chunks = Vec::new();
-        let mut start_time = 0.0;
-        let mut chunk_index = 0;
-
-        while start_time < duration {
-            let chunk_path = format!("{}/chunk_{:03}.mp3", segment_dir, chunk_index);
-
-            // Calculate actual start and end times with overlap
-            let actual_start = if chunk_index == 0 {
-                // First chunk starts at the beginning
-                0.0
-            } else {
-                // Subsequent chunks start 10 seconds earlier for overlap
-                (start_time - self.config.grace_period_seconds as f64).max(0.0)
-            };
-
-            let theoretical_end = start_time + chunk_info.seconds_per_chunk;
-            let actual_end = if theoretical_end >= duration {
-                // Last chunk ends at the end of the file
-                duration
-            } else {
-                // Add 10 seconds for overlap, but don't exceed file duration
-                (theoretical_end + self.config.grace_period_seconds as f64).min(duration)
-            };
+    /// Find silences between `from` and `to` with ffmpeg's `silencedetect` filter
+    async fn detect_silences(&self, input_path: &Path, from: f64, to: f64) -> Result<Vec<Silence>> {
+        #[cfg(feature = "native-audio")]
+        if AudioEngine::detect() == AudioEngine::Native {
+            let input_path = input_path.to_owned();
+            let (noise_db, min_seconds) = (
+                self.config.silence_noise_db,
+                self.config.silence_min_seconds,
+            );
+            return tokio::task::spawn_blocking(move || {
+                crate::native_audio::detect_silences(&input_path, from, to, noise_db, min_seconds)
+            })
+            .await?;
+        }
 
-            let chunk_duration = actual_end - actual_start;
+        let filter = format!(
+            "silencedetect=noise={}dB:d={}",
+            self.config.silence_noise_db, self.config.silence_min_seconds
+        );
+        let output = Command::new("ffmpeg")
+            .args([
+                "-ss",
+                &from.to_string(),
+                "-t",
+                &(to - from).to_string(),
+                "-i",
+                input_path.to_str().context("Invalid file path encoding")?,
+                "-vn",
+                "-af",
+                &filter,
+                "-f",
+                "null",
+                "-",
+            ])
+            .output()
+            .await
+            .map_err(media::spawn_error)?;
 
-            if chunk_duration > 1.0 {
-                self.create_single_chunk(input_path, &chunk_path, actual_start, chunk_duration)?;
-                chunks.push(chunk_path);
-                start_time += chunk_info.seconds_per_chunk;
-                chunk_index += 1;
-            } else {
-                break;
-            }
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("FFmpeg error when detecting silence: {}", error);
         }
 
-        Ok(chunks)
+        // silencedetect reports through the log on stderr, relative to the seek point
+        let silences = parse_silencedetect(&String::from_utf8_lossy(&output.stderr));
+        Ok(silences
+            .into_iter()
+            .map(|silence| Silence {
+                start: silence.start + from,
+                end: silence.end + from,
+            })
+            .collect())
+    }
+
+    /// Most bytes the whole input can take once written as chunks
+    fn encoded_size(&self, engine: AudioEngine, duration: f64) -> u64 {
+        let bytes_per_second = engine
+            .encoded_bytes_per_second(self.config.preprocess, self.config.preprocess_bitrate_kbps);
+        (duration * bytes_per_second as f64) as u64
     }
 
     fn calculate_chunk_parameters(&self, total_size: u64, duration: f64) -> ChunkInfo {
@@ -121,39 +284,83 @@ impl AudioChunker {
 
         ChunkInfo { seconds_per_chunk }
     }
+}
 
-    fn create_single_chunk(
-        &self,
-        input_path: &Path,
-        chunk_path: &str,
-        start_time: f64,
-        chunk_duration: f64,
-    ) -> Result<()> {
-        let output = Command::new("ffmpeg")
-            .args([
-                "-y", // Overwrite output files without asking
-                "-i",
-                input_path.to_str().context("Invalid file path encoding")?,
-                "-ss",
-                &start_time.to_string(),
-                "-t",
-                &chunk_duration.to_string(),
-                "-c:a",
-                "libmp3lame", // Encode to MP3 (handles WAV and other input formats)
-                "-q:a",
-                "2", // High quality VBR encoding
-                "-loglevel",
-                "error",
-                chunk_path,
-            ])
-            .output()?;
+/// Works out the time range of every chunk, one at a time.
+///
+/// Each chunk is cut at the last silence before its size target, searching back
+/// up to `silence_search_seconds`. Only when no silence is found there is the cut
+/// made at the target itself, with a grace period of overlap on both sides so
+/// that words cut in half can be recovered when merging.
+struct ChunkPlanner {
+    duration: f64,
+    seconds_per_chunk: f64,
+    grace: f64,
+    window: f64,
+    start_time: f64,
+    overlap_previous: bool,
+    finished: bool,
+}
 
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("FFmpeg error when creating chunk: {}", error);
+impl ChunkPlanner {
+    fn new(config: &Config, duration: f64, seconds_per_chunk: f64) -> Self {
+        Self {
+            duration,
+            seconds_per_chunk,
+            grace: config.grace_period_seconds as f64,
+            window: config.silence_search_seconds as f64,
+            start_time: 0.0,
+            overlap_previous: false,
+            finished: false,
         }
+    }
 
-        Ok(())
+    /// Stretch of audio the next cut can fall in, or `None` when the next
+    /// chunk runs to the end of the file
+    fn search_window(&self) -> Option<(f64, f64)> {
+        let target_end = self.start_time + self.seconds_per_chunk;
+        if self.finished || target_end >= self.duration {
+            return None;
+        }
+        Some(((target_end - self.window).max(self.start_time), target_end))
+    }
+
+    /// Range of the next chunk, given the silences found in its search window
+    fn next_chunk(&mut self, silences: &[Silence]) -> Option<(f64, f64)> {
+        if self.finished || self.start_time >= self.duration {
+            return None;
+        }
+
+        // Only blind cuts need an overlap with the previous chunk
+        let actual_start = if self.overlap_previous {
+            (self.start_time - self.grace).max(0.0)
+        } else {
+            self.start_time
+        };
+
+        let target_end = self.start_time + self.seconds_per_chunk;
+        let silence_cut = silences.iter().rev().map(Silence::midpoint).find(|&cut| {
+            cut <= target_end && cut >= target_end - self.window && cut > self.start_time + 1.0
+        });
+
+        let (actual_end, next_start) = if target_end >= self.duration {
+            // Last chunk ends at the end of the file
+            (self.duration, self.duration)
+        } else if let Some(cut) = silence_cut {
+            (cut, cut)
+        } else {
+            // Add the grace period for overlap, but don't exceed file duration
+            ((target_end + self.grace).min(self.duration), target_end)
+        };
+
+        if actual_end - actual_start <= 1.0 {
+            self.finished = true;
+            return None;
+        }
+
+        self.overlap_previous = silence_cut.is_none();
+        self.start_time = next_start;
+        Some((actual_start, actual_end))
     }
 }
 
@@ -161,9 +368,36 @@ struct ChunkInfo {
     seconds_per_chunk: f64,
 }
 
+/// Parse `silence_start` / `silence_end` pairs from ffmpeg's silencedetect log
+fn parse_silencedetect(log: &str) -> Vec<Silence> {
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+
+    let mut silences = Vec::new();
+    let mut current_start = None;
+
+    for line in log.lines() {
+        if let Some(start) = value_after(line, "silence_start:") {
+            current_start = Some(start);
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            if let Some(start) = current_start.take() {
+                silences.push(Silence { start, end });
+            }
+        }
+    }
+
+    silences
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::media::Preprocess;
+
+    /// Average size of a second of `-q:a 2` MP3
+    const VBR_MP3_BYTES_PER_SECOND: f64 = 190_000.0 / 8.0;
 
     #[test]
     fn test_calculate_chunk_parameters() {
@@ -178,8 +412,36 @@ mod tests {
 
         assert!(chunk_info.seconds_per_chunk > 0.0);
 
-        // With 100MB file and ~23MB target chunk size, should have reasonable chunk duration
-        assert!(chunk_info.seconds_per_chunk > 200.0 && chunk_info.seconds_per_chunk < 300.0);
+        // With 100MB file and 20MB target chunk size, each chunk covers exactly
+        // a fifth of the audio
+        assert_eq!(chunk_info.seconds_per_chunk, 200.0);
+    }
+
+    #[test]
+    fn test_low_bitrate_input_makes_chunks_within_limit() {
+        let config = Config::default();
+        let chunker = AudioChunker::new(&config);
+
+        // An hour-long 64 kbps voice memo is under 30MB, but its chunks are
+        // written as high quality MP3
+        let duration = 3600.0;
+        let input_size = (duration * 64_000.0 / 8.0) as u64;
+        let from_input = chunker.calculate_chunk_parameters(input_size, duration);
+        let total_size = chunker.encoded_size(AudioEngine::Ffmpeg, duration);
+        let chunk_info = chunker.calculate_chunk_parameters(total_size, duration);
+
+        let chunk_bytes = |seconds: f64| seconds * VBR_MP3_BYTES_PER_SECOND;
+        assert!(chunk_bytes(from_input.seconds_per_chunk) > config.max_file_size_bytes() as f64);
+        assert!(chunk_bytes(chunk_info.seconds_per_chunk) <= config.chunk_size_bytes() as f64);
+
+        // Pre-processed chunks are small, so they can cover more audio
+        let chunker = AudioChunker::new(&Config {
+            preprocess: Preprocess::Opus,
+            ..config
+        });
+        let total_size = chunker.encoded_size(AudioEngine::Ffmpeg, duration);
+        let opus = chunker.calculate_chunk_parameters(total_size, duration);
+        assert!(opus.seconds_per_chunk > chunk_info.seconds_per_chunk);
     }
 
     #[test]
@@ -188,7 +450,7 @@ mod tests {
         let chunker = AudioChunker::new(&config);
 
         assert_eq!(chunker.config.chunk_size_mb, config.chunk_size_mb);
-        assert_eq!(chunker.config.temp_dir_name, config.temp_dir_name);
+        assert_eq!(chunker.config.cache_dir, config.cache_dir);
     }
 
     #[test]
@@ -207,4 +469,112 @@ mod tests {
         assert!(chunk_info.seconds_per_chunk > 0.0);
         assert_eq!(config.grace_period_seconds, 10);
     }
+
+    fn test_chunker() -> AudioChunker {
+        AudioChunker::new(&Config {
+            grace_period_seconds: 10,
+            silence_search_seconds: 30,
+            ..Config::default()
+        })
+    }
+
+    impl AudioChunker {
+        /// Plan every chunk at once from silences found across the whole file
+        fn plan_chunks(
+            &self,
+            duration: f64,
+            seconds_per_chunk: f64,
+            silences: &[Silence],
+        ) -> Vec<(f64, f64)> {
+            let mut planner = ChunkPlanner::new(&self.config, duration, seconds_per_chunk);
+            std::iter::from_fn(|| planner.next_chunk(silences)).collect()
+        }
+    }
+
+    #[test]
+    fn test_planner_search_windows_follow_cuts() {
+        let mut planner = ChunkPlanner::new(&test_chunker().config, 250.0, 100.0);
+        assert_eq!(planner.search_window(), Some((70.0, 100.0)));
+
+        let silence = [Silence {
+            start: 94.0,
+            end: 96.0,
+        }];
+        assert_eq!(planner.next_chunk(&silence), Some((0.0, 95.0)));
+
+        // The next window is placed after the cut that was actually made
+        assert_eq!(planner.search_window(), Some((165.0, 195.0)));
+        assert_eq!(planner.next_chunk(&[]), Some((95.0, 205.0)));
+
+        // The last chunk runs to the end, so there is nothing to search
+        assert_eq!(planner.search_window(), None);
+        assert_eq!(planner.next_chunk(&[]), Some((185.0, 250.0)));
+        assert_eq!(planner.next_chunk(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_silencedetect() {
+        let log = "\
+[silencedetect @ 0x6000] silence_start: 12.5
+[silencedetect @ 0x6000] silence_end: 13.75 | silence_duration: 1.25
+size=N/A time=00:00:20.00 bitrate=N/A speed= 500x
+[silencedetect @ 0x6000] silence_start: 18
+[silencedetect @ 0x6000] silence_end: 19 | silence_duration: 1
+[silencedetect @ 0x6000] silence_start: 19.5";
+
+        assert_eq!(
+            parse_silencedetect(log),
+            vec![
+                Silence {
+                    start: 12.5,
+                    end: 13.75
+                },
+                Silence {
+                    start: 18.0,
+                    end: 19.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_chunks_without_silence_overlaps() {
+        let ranges = test_chunker().plan_chunks(250.0, 100.0, &[]);
+        assert_eq!(ranges, vec![(0.0, 110.0), (90.0, 210.0), (190.0, 250.0)]);
+    }
+
+    #[test]
+    fn test_plan_chunks_cuts_at_silence() {
+        let silences = [
+            Silence {
+                start: 50.0,
+                end: 51.0,
+            },
+            Silence {
+                start: 94.0,
+                end: 96.0,
+            },
+            // Beyond the target of the first chunk
+            Silence {
+                start: 101.0,
+                end: 102.0,
+            },
+        ];
+
+        let ranges = test_chunker().plan_chunks(250.0, 100.0, &silences);
+        // First cut at the silence closest before 100s, no overlap after it.
+        // No silence within 30s before the second target (195s), so that cut overlaps.
+        assert_eq!(ranges, vec![(0.0, 95.0), (95.0, 205.0), (185.0, 250.0)]);
+    }
+
+    #[test]
+    fn test_plan_chunks_ignores_silence_outside_window() {
+        let silences = [Silence {
+            start: 40.0,
+            end: 42.0,
+        }];
+
+        let ranges = test_chunker().plan_chunks(150.0, 100.0, &silences);
+        assert_eq!(ranges, vec![(0.0, 110.0), (90.0, 150.0)]);
+    }
 }
@@ -1,76 +1,132 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use reqwest::multipart::{Form, Part};
 use std::path::Path;
 use tokio::fs;
 
+use crate::backend::{BackendKind, TranscriptionBackend, OPENAI_BASE_URL};
+use crate::error::MurmurError;
+use crate::media;
+use crate::output::OutputFormat;
+use crate::request::TranscriptionRequest;
+use crate::retry::RetryPolicy;
+use crate::transcription::Transcript;
 use crate::utils::{self, Config};
-use crate::Args;
 
-/// OpenAI Whisper API client
+/// OpenAI Whisper API client, also used for OpenAI-compatible servers
 pub struct WhisperClient {
     client: reqwest::Client,
+    /// The kind of server `base_url` points at
+    backend: BackendKind,
     api_key: String,
     base_url: String,
+    transcription_model: String,
+    enhancement_model: String,
+    retry: RetryPolicy,
 }
 
 impl WhisperClient {
     pub fn new(api_key: String, config: &Config) -> Result<Self> {
+        let client = Self::with_base_url(api_key, OPENAI_BASE_URL.to_string(), config)?;
+        Ok(Self {
+            backend: BackendKind::OpenAi,
+            ..client
+        })
+    }
+
+    /// Create a client that talks to the configured backend's server at
+    /// `base_url`, such as a proxy or an OpenAI-compatible server
+    pub fn with_base_url(api_key: String, base_url: String, config: &Config) -> Result<Self> {
         let client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(
                 config.whisper_timeout_seconds,
             ))
             .build()?;
 
+        // whisper.cpp has its own client, so this one talks to the OpenAI-style
+        // endpoints of a whisper.cpp server
+        let backend = match config.backend {
+            BackendKind::WhisperCpp => BackendKind::OpenAiCompatible,
+            backend => backend,
+        };
+
         Ok(Self {
             client,
+            backend,
             api_key,
-            base_url: "https://api.openai.com/v1".to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            transcription_model: config.transcription_model.clone(),
+            enhancement_model: config.enhancement_model.clone(),
+            retry: RetryPolicy::new(config),
         })
     }
 
-    pub async fn transcribe(&self, args: &Args) -> Result<String> {
-        let input_path = args
-            .input
-            .as_ref()
-            .context("Input file path is required for transcription")?;
+    /// Whether requests can authenticate: every backend this client talks to
+    /// needs an API key
+    pub fn has_api_key(&self) -> bool {
+        !self.api_key.is_empty()
+    }
+
+    /// Server the requests go to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
 
+    /// Transcribe `audio`, which may be the request's input or a chunk of it
+    pub async fn transcribe(
+        &self,
+        audio: &Path,
+        request: &TranscriptionRequest,
+    ) -> Result<Transcript> {
         // Validate input file exists
-        if !input_path.exists() {
-            anyhow::bail!("Input file {:?} does not exist", input_path);
+        if !audio.exists() {
+            return Err(MurmurError::UnsupportedInput(format!(
+                "Input file {:?} does not exist",
+                audio
+            ))
+            .into());
         }
 
-        let file_size = utils::get_file_size(input_path).await?;
+        let file_size = utils::get_file_size(audio).await?;
         let file_size_mb = utils::bytes_to_mb(file_size);
 
         // Check size limit for direct API calls
         if file_size_mb > utils::MAX_FILE_SIZE_MB as f64 {
-            anyhow::bail!(
+            return Err(MurmurError::UnsupportedInput(format!(
                 "File size ({:.2} MB) exceeds {} MB limit",
                 file_size_mb,
                 utils::MAX_FILE_SIZE_MB
-            );
+            ))
+            .into());
         }
 
         // Print processing message only for regular files (not chunks or recordings)
-        if !self.is_chunk_file(input_path) && !self.is_temp_recording(input_path) {
+        if !self.is_chunk_file(audio) && !self.is_temp_recording(audio) {
             println!("Processing file ({:.1} MB)...", file_size_mb);
         }
 
         // Read file content
-        let file_bytes = fs::read(input_path)
-            .await
-            .context("Failed to read audio file")?;
+        let file_bytes = fs::read(audio).await.context("Failed to read audio file")?;
 
         // Build multipart form
-        let file_name = utils::get_filename_or_default(input_path, "audio.mp3");
-        let form = self.build_form(&file_name, file_bytes, &args.language)?;
+        let file_name = utils::get_filename_or_default(audio, "audio.mp3");
+
+        // Send request, rebuilding the form for every retry
+        let response_text = self
+            .send_transcription_request(&file_name, &file_bytes, request)
+            .await?;
 
-        // Send request
-        self.send_transcription_request(form).await
+        if request.format.needs_segments() {
+            Transcript::from_verbose_json(&response_text)
+        } else {
+            Ok(Transcript::from_text(response_text))
+        }
     }
 
     fn is_chunk_file(&self, path: &Path) -> bool {
-        path.to_string_lossy().contains("murmur_audio_chunks")
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("chunk_"))
     }
 
     fn is_temp_recording(&self, path: &Path) -> bool {
@@ -81,94 +137,249 @@ impl WhisperClient {
         &self,
         file_name: &str,
         file_bytes: Vec<u8>,
-        language: &Option<String>,
+        request: &TranscriptionRequest,
     ) -> Result<Form> {
-        let mut form = Form::new()
-            .text("model", "whisper-1")
-            .text("response_format", "text")
-            .text("temperature", "0");
-
-        if let Some(lang) = language {
-            form = form.text("language", lang.clone());
-        }
+        let model = request
+            .model
+            .clone()
+            .unwrap_or_else(|| self.transcription_model.clone());
+        let form = request_fields(Form::new().text("model", model), request);
 
         let file_part = Part::bytes(file_bytes)
             .file_name(file_name.to_string())
-            .mime_str("audio/mpeg")?;
+            .mime_str(media::mime_type(Path::new(file_name)))?;
 
-        form = form.part("file", file_part);
-        Ok(form)
+        Ok(form.part("file", file_part))
     }
 
-    async fn send_transcription_request(&self, form: Form) -> Result<String> {
+    async fn send_transcription_request(
+        &self,
+        file_name: &str,
+        file_bytes: &[u8],
+        request: &TranscriptionRequest,
+    ) -> Result<String> {
         let response = self
-            .client
-            .post(format!("{}/audio/transcriptions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .multipart(form)
-            .send()
-            .await
-            .context("Failed to send request")?;
+            .retry
+            .send("transcription", || {
+                let form = self.build_form(file_name, file_bytes.to_vec(), request)?;
+                Ok(self
+                    .client
+                    .post(format!("{}/audio/transcriptions", self.base_url))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .multipart(form))
+            })
+            .await?;
 
         Self::handle_api_response(response, "transcription").await
     }
 
+    /// Enhance text, waiting for the whole reply
     pub async fn enhance_text(&self, prompt: &str) -> Result<String> {
+        self.enhance_text_streaming(prompt, |_| {}).await
+    }
+
+    /// Enhance text, passing each piece of the reply to `on_delta` as it streams
+    /// in. Returns the whole reply.
+    pub async fn enhance_text_streaming(
+        &self,
+        prompt: &str,
+        mut on_delta: impl FnMut(&str),
+    ) -> Result<String> {
         let request_body = serde_json::json!({
-            "model": "gpt-3.5-turbo",
+            "model": self.enhancement_model,
             "messages": [
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 2000,
-            "temperature": 0.3
+            "max_tokens": utils::ENHANCEMENT_MAX_TOKENS,
+            "temperature": 0.3,
+            "stream": true
         });
 
         let response = self
-            .client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .context("Failed to send enhancement request")?;
-
-        // Handle API response
-        let response_text = Self::handle_api_response(response, "text enhancement").await?;
-
-        // Parse JSON response for ChatGPT API
-        let response_json: serde_json::Value =
-            serde_json::from_str(&response_text).context("Failed to parse enhancement response")?;
+            .retry
+            .send("enhancement", || {
+                Ok(self
+                    .client
+                    .post(format!("{}/chat/completions", self.base_url))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("Content-Type", "application/json")
+                    .json(&request_body))
+            })
+            .await?;
+        let mut response = Self::check_api_response(response, "text enhancement").await?;
+
+        // Servers that don't support streaming answer with a single completion
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_event_stream {
+            let response_text = response
+                .text()
+                .await
+                .context("Failed to read API response")?;
+            let enhanced_text = parse_completion(&response_text)?;
+            on_delta(&enhanced_text);
+            return Ok(enhanced_text);
+        }
 
-        let enhanced_text = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .context("Invalid response format from enhancement API")?
-            .trim()
-            .to_string();
+        let mut events = SseDecoder::default();
+        let mut enhanced_text = String::new();
+        let mut finish_reason = None;
+        while let Some(bytes) = response.chunk().await.with_context(|| {
+            MurmurError::Network("Enhancement stream was interrupted".to_string())
+        })? {
+            for data in events.push(&bytes) {
+                if data == "[DONE]" {
+                    check_finish_reason(finish_reason.as_deref())?;
+                    return Ok(enhanced_text.trim().to_string());
+                }
+                let event: serde_json::Value =
+                    serde_json::from_str(&data).context("Failed to parse enhancement stream")?;
+                if let Some(delta) = event["choices"][0]["delta"]["content"].as_str() {
+                    enhanced_text.push_str(delta);
+                    on_delta(delta);
+                }
+                if let Some(reason) = event["choices"][0]["finish_reason"].as_str() {
+                    finish_reason = Some(reason.to_string());
+                }
+            }
+        }
 
-        Ok(enhanced_text)
+        check_finish_reason(finish_reason.as_deref())?;
+        Ok(enhanced_text.trim().to_string())
     }
 
     /// Generic API response handler for both transcription and enhancement APIs
-    async fn handle_api_response(response: reqwest::Response, api_type: &str) -> Result<String> {
+    pub(crate) async fn handle_api_response(
+        response: reqwest::Response,
+        api_type: &str,
+    ) -> Result<String> {
+        Self::check_api_response(response, api_type)
+            .await?
+            .text()
+            .await
+            .context("Failed to read API response")
+    }
+
+    /// Turn an error status into the matching error, leaving the body of a
+    /// successful response unread
+    async fn check_api_response(
+        response: reqwest::Response,
+        api_type: &str,
+    ) -> Result<reqwest::Response> {
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            let error_message = match status.as_u16() {
-                401 => format!("Invalid API key for {}. Please check your OPENAI_API_KEY environment variable.", api_type),
-                429 => format!("Rate limit exceeded for {}. Please wait a moment and try again.", api_type),
-                413 => "File too large for API. This shouldn't happen with proper chunking.".to_string(),
-                400 => format!("Bad request for {}: {}", api_type, error_text),
-                500..=599 => format!("OpenAI server error during {}. Please try again later.", api_type),
-                _ => format!("{} API error ({}): {}", api_type, status, error_text),
+            let error = match status.as_u16() {
+                401 => MurmurError::Auth(format!("Invalid API key for {}. Please check your API key configuration.", api_type)),
+                403 => MurmurError::Auth(format!("Access denied for {}: {}", api_type, error_text)),
+                429 => MurmurError::RateLimit(format!("Rate limit exceeded for {} after all retries. Please wait a moment and try again.", api_type)),
+                413 => MurmurError::UnsupportedInput("File too large for API. This shouldn't happen with proper chunking.".to_string()),
+                400 => anyhow::bail!("Bad request for {}: {}", api_type, error_text),
+                500..=599 => MurmurError::Network(format!("OpenAI server error during {}. Please try again later.", api_type)),
+                _ => anyhow::bail!("{} API error ({}): {}", api_type, status, error_text),
             };
-            anyhow::bail!("{}", error_message);
+            return Err(error.into());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Text of a complete, non-streamed chat completion
+fn parse_completion(body: &str) -> Result<String> {
+    let response_json: serde_json::Value =
+        serde_json::from_str(body).context("Failed to parse enhancement response")?;
+    check_finish_reason(response_json["choices"][0]["finish_reason"].as_str())?;
+
+    let enhanced_text = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .context("Invalid response format from enhancement API")?
+        .trim()
+        .to_string();
+
+    Ok(enhanced_text)
+}
+
+/// Fail on a reply the model stopped at the token limit, since the end of the
+/// text would be missing
+fn check_finish_reason(finish_reason: Option<&str>) -> Result<()> {
+    if finish_reason == Some("length") {
+        anyhow::bail!(
+            "Enhancement reply was cut off at the {} token limit",
+            utils::ENHANCEMENT_MAX_TOKENS
+        );
+    }
+    Ok(())
+}
+
+/// Splits a server-sent event stream into the `data` of its events. Bytes are
+/// buffered up to the end of each line, so characters split between network
+/// chunks come out whole.
+#[derive(Default)]
+struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    /// Feed the next bytes of the stream, returning the data lines they complete
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut data = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(value) = line.trim_end_matches(['\n', '\r']).strip_prefix("data:") {
+                data.push(value.trim_start().to_string());
+            }
         }
+        data
+    }
+}
 
-        response.text().await.context("Failed to read API response")
+/// API `response_format` to request for an output format
+pub(crate) fn response_format(format: OutputFormat) -> &'static str {
+    if format.needs_segments() {
+        "verbose_json"
+    } else {
+        "text"
+    }
+}
+
+/// Add the form fields every transcription endpoint shares
+pub(crate) fn request_fields(form: Form, request: &TranscriptionRequest) -> Form {
+    let temperature = request.temperature.unwrap_or(0.0);
+    let mut form = form
+        .text("response_format", response_format(request.format))
+        .text("temperature", temperature.to_string());
+
+    if let Some(language) = &request.language {
+        form = form.text("language", language.clone());
+    }
+    if let Some(prompt) = &request.prompt {
+        form = form.text("prompt", prompt.clone());
+    }
+    form
+}
+
+#[async_trait]
+impl TranscriptionBackend for WhisperClient {
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    async fn transcribe(&self, audio: &Path, request: &TranscriptionRequest) -> Result<Transcript> {
+        WhisperClient::transcribe(self, audio, request).await
+    }
+
+    fn rate_limit_count(&self) -> u64 {
+        self.retry.rate_limit_count()
     }
 }
 
@@ -177,7 +388,7 @@ mod tests {
     use super::*;
     use std::io::Write;
     use tempfile::NamedTempFile;
-    use wiremock::matchers::{method, path};
+    use wiremock::matchers::{body_string_contains, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -187,6 +398,8 @@ mod tests {
 
         Mock::given(method("POST"))
             .and(path("/audio/transcriptions"))
+            .and(body_string_contains("gpt-4o-transcribe"))
+            .and(body_string_contains("Murmur, FFmpeg"))
             .respond_with(ResponseTemplate::new(200).set_body_string(response_body))
             .mount(&mock_server)
             .await;
@@ -198,18 +411,52 @@ mod tests {
 
         let client = WhisperClient {
             client: reqwest::Client::new(),
+            backend: BackendKind::OpenAi,
             api_key: "test_key".to_string(),
             base_url: mock_server.uri(),
+            transcription_model: "whisper-1".to_string(),
+            enhancement_model: "gpt-3.5-turbo".to_string(),
+            retry: RetryPolicy::new(&Config::default()),
         };
 
-        let args = Args {
-            input: Some(temp_file.path().to_path_buf()),
-            language: Some("en".to_string()),
-        };
+        let request = TranscriptionRequest::file(temp_file.path())
+            .language("en")
+            .model("gpt-4o-transcribe")
+            .prompt("Murmur, FFmpeg")
+            .build();
 
-        let result = client.transcribe(&args).await;
+        let result = client.transcribe(temp_file.path(), &request).await;
         assert!(result.is_ok(), "Transcribe failed: {:?}", result.err());
-        assert_eq!(result.unwrap(), response_body);
+        assert_eq!(result.unwrap().text, response_body);
+    }
+
+    #[tokio::test]
+    async fn test_unusable_input_is_unsupported() {
+        let client = test_client("http://localhost:1".to_string());
+        let request = TranscriptionRequest::file("missing.mp3").build();
+        let error = client
+            .transcribe(Path::new("missing.mp3"), &request)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::UnsupportedInput(_))
+        ));
+
+        // Sparse, so this doesn't write 26 MB
+        let temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .as_file()
+            .set_len((utils::MAX_FILE_SIZE_MB + 1) * 1024 * 1024)
+            .unwrap();
+        let error = client
+            .transcribe(temp_file.path(), &request)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::UnsupportedInput(_))
+        ));
     }
 
     #[test]
@@ -217,14 +464,265 @@ mod tests {
         let _config = Config::default();
         let client = WhisperClient {
             client: reqwest::Client::new(),
+            backend: BackendKind::OpenAi,
             api_key: "test".to_string(),
             base_url: "test".to_string(),
+            transcription_model: "whisper-1".to_string(),
+            enhancement_model: "gpt-3.5-turbo".to_string(),
+            retry: RetryPolicy::new(&Config::default()),
         };
 
-        let chunk_path = std::path::Path::new("/tmp/murmur_audio_chunks/chunk_001.mp3");
+        let chunk_path = std::path::Path::new("/home/me/.cache/murmur/chunks/ab12/chunk_001.mp3");
         let normal_path = std::path::Path::new("/tmp/audio.mp3");
 
         assert!(client.is_chunk_file(chunk_path));
         assert!(!client.is_chunk_file(normal_path));
     }
+
+    #[test]
+    fn test_name_follows_configured_backend() {
+        let config = Config::default();
+        let client =
+            WhisperClient::with_base_url(String::new(), format!("{}/", OPENAI_BASE_URL), &config)
+                .unwrap();
+        assert_eq!(client.name(), "openai");
+
+        // A proxy in front of OpenAI is still OpenAI
+        let proxy =
+            WhisperClient::with_base_url(String::new(), "http://proxy:3128/v1".into(), &config)
+                .unwrap();
+        assert_eq!(proxy.name(), "openai");
+
+        let config = Config {
+            backend: BackendKind::OpenAiCompatible,
+            ..Config::default()
+        };
+        let client =
+            WhisperClient::with_base_url(String::new(), OPENAI_BASE_URL.into(), &config).unwrap();
+        assert_eq!(client.name(), "openai-compatible");
+
+        let config = Config {
+            backend: BackendKind::WhisperCpp,
+            ..Config::default()
+        };
+        assert_eq!(
+            WhisperClient::new(String::new(), &config).unwrap().name(),
+            "openai"
+        );
+    }
+
+    fn test_client(base_url: String) -> WhisperClient {
+        WhisperClient {
+            client: reqwest::Client::new(),
+            backend: BackendKind::OpenAi,
+            api_key: "test_key".to_string(),
+            base_url,
+            transcription_model: "whisper-1".to_string(),
+            enhancement_model: "gpt-3.5-turbo".to_string(),
+            retry: RetryPolicy {
+                max_attempts: 3,
+                base_delay: std::time::Duration::from_millis(10),
+                max_delay: std::time::Duration::from_millis(50),
+                rate_limited: Default::default(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_retries_after_rate_limit() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/audio/transcriptions"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/audio/transcriptions"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("After retry."))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[0u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+
+        let client = test_client(mock_server.uri());
+        let request = TranscriptionRequest::file(temp_file.path()).build();
+
+        let transcript = client.transcribe(temp_file.path(), &request).await;
+        assert_eq!(transcript.unwrap().text, "After retry.");
+        assert_eq!(TranscriptionBackend::rate_limit_count(&client), 1);
+    }
+
+    #[tokio::test]
+    async fn test_enhance_text_retries_server_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": " Enhanced. "}}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        assert_eq!(client.enhance_text("raw").await.unwrap(), "Enhanced.");
+    }
+
+    #[tokio::test]
+    async fn test_enhance_text_streaming() {
+        let mock_server = MockServer::start().await;
+        let events = [
+            r#"{"choices":[{"delta":{"role":"assistant","content":""}}]}"#,
+            r#"{"choices":[{"delta":{"content":"Hello"}}]}"#,
+            r#"{"choices":[{"delta":{"content":" wörld."}}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+            "[DONE]",
+        ];
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(r#""stream":true"#))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        let mut deltas = Vec::new();
+        let text = client
+            .enhance_text_streaming("raw", |delta| deltas.push(delta.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello wörld.");
+        assert_eq!(deltas.concat(), "Hello wörld.");
+    }
+
+    #[tokio::test]
+    async fn test_enhance_text_cut_off() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": "The first half"}, "finish_reason": "length"}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        let error = client.enhance_text("raw").await.unwrap_err();
+        assert!(error.to_string().contains("cut off"));
+
+        // Also when the reason comes in the last event of a stream
+        let mock_server = MockServer::start().await;
+        let body: String = [
+            r#"{"choices":[{"delta":{"content":"The first half"}}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"length"}]}"#,
+            "[DONE]",
+        ]
+        .iter()
+        .map(|event| format!("data: {}\n\n", event))
+        .collect();
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        let error = client.enhance_text("raw").await.unwrap_err();
+        assert!(error.to_string().contains("cut off"));
+    }
+
+    #[test]
+    fn test_sse_decoder() {
+        let mut decoder = SseDecoder::default();
+        let event = "data: {\"text\":\"é\"}\r\n\r\n: keep-alive\n\ndata: [DONE]\n\n".as_bytes();
+
+        // Split inside the two-byte "é"
+        let split = event.iter().position(|&b| b == 0xc3).unwrap() + 1;
+        assert!(decoder.push(&event[..split]).is_empty());
+        assert_eq!(
+            decoder.push(&event[split..]),
+            vec!["{\"text\":\"é\"}".to_string(), "[DONE]".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        let error = client.enhance_text("raw").await.unwrap_err();
+        assert!(error.to_string().contains("Rate limit exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_auth_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = test_client(mock_server.uri());
+        let error = client.enhance_text("raw").await.unwrap_err();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::Auth(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_with_segments() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/audio/transcriptions"))
+            .and(body_string_contains("verbose_json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "text": "Hello there.",
+                "segments": [{"start": 0.0, "end": 1.5, "text": " Hello there."}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[0u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+
+        let client = test_client(mock_server.uri());
+        let request = TranscriptionRequest::file(temp_file.path())
+            .format(OutputFormat::Srt)
+            .build();
+
+        let transcript = client.transcribe(temp_file.path(), &request).await.unwrap();
+        assert_eq!(transcript.text, "Hello there.");
+        assert_eq!(transcript.segments.len(), 1);
+        assert_eq!(transcript.segments[0].end, 1.5);
+    }
 }
@@ -0,0 +1,265 @@
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::backend::BackendKind;
+use crate::utils::{DEFAULT_JOBS, MAX_ADAPTIVE_JOBS};
+
+/// How many chunks to transcribe at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jobs {
+    /// Always this many
+    Fixed(usize),
+    /// Start at a moderate level, back off on rate limits and speed up again
+    /// after sustained success
+    Auto,
+}
+
+impl Jobs {
+    /// Default for a backend: local servers work through one request at a time
+    pub fn default_for(backend: BackendKind) -> Self {
+        match backend {
+            BackendKind::WhisperCpp => Jobs::Fixed(1),
+            BackendKind::OpenAi | BackendKind::OpenAiCompatible => Jobs::Auto,
+        }
+    }
+}
+
+impl FromStr for Jobs {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(Jobs::Auto);
+        }
+        match value.parse::<usize>() {
+            Ok(jobs) if jobs > 0 => Ok(Jobs::Fixed(jobs)),
+            _ => Err(format!(
+                "Invalid jobs value '{}' (use a positive number or 'auto')",
+                value
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Jobs {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(usize),
+            Text(String),
+        }
+
+        let value = match Raw::deserialize(deserializer)? {
+            Raw::Number(jobs) => jobs.to_string(),
+            Raw::Text(text) => text,
+        };
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Limits how many chunks are transcribed at once.
+///
+/// In adaptive mode the limit follows AIMD: it is halved whenever the backend
+/// reports new rate limits, and raised by one after as many successes in a
+/// row as the current limit.
+pub struct ConcurrencyLimiter {
+    semaphore: Semaphore,
+    adaptive: bool,
+    max: usize,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: usize,
+    successes: usize,
+    rate_limits_seen: u64,
+    /// Permits to take back as running chunks finish, after a decrease
+    pending_shrink: usize,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(jobs: Jobs) -> Self {
+        let (limit, max, adaptive) = match jobs {
+            Jobs::Fixed(jobs) => (jobs.max(1), jobs.max(1), false),
+            Jobs::Auto => (DEFAULT_JOBS, MAX_ADAPTIVE_JOBS, true),
+        };
+
+        Self {
+            semaphore: Semaphore::new(limit),
+            adaptive,
+            max,
+            state: Mutex::new(LimiterState {
+                limit,
+                successes: 0,
+                rate_limits_seen: 0,
+                pending_shrink: 0,
+            }),
+        }
+    }
+
+    /// Most chunks that can ever run at once
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Current limit
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Wait for a free slot
+    pub async fn acquire(&self) -> ChunkPermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("Concurrency semaphore is never closed");
+        ChunkPermit {
+            permit: Some(permit),
+            limiter: self,
+        }
+    }
+
+    /// Adjust the limit after a chunk finished. `rate_limits_seen` is the
+    /// backend's running count of rate-limited responses.
+    pub fn record(&self, rate_limits_seen: u64, succeeded: bool) {
+        if !self.adaptive {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if rate_limits_seen > state.rate_limits_seen {
+            state.rate_limits_seen = rate_limits_seen;
+            state.successes = 0;
+
+            let new_limit = (state.limit / 2).max(1);
+            let shrink = state.limit - new_limit;
+            state.limit = new_limit;
+
+            // Take back idle slots now and busy ones as their chunks finish
+            for _ in 0..shrink {
+                match self.semaphore.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => state.pending_shrink += 1,
+                }
+            }
+        } else if succeeded {
+            state.successes += 1;
+            if state.successes >= state.limit && state.limit < self.max {
+                state.limit += 1;
+                state.successes = 0;
+                if state.pending_shrink > 0 {
+                    state.pending_shrink -= 1;
+                } else {
+                    self.semaphore.add_permits(1);
+                }
+            }
+        }
+    }
+}
+
+/// A slot for one chunk, given back when dropped
+pub struct ChunkPermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    limiter: &'a ConcurrencyLimiter,
+}
+
+impl Drop for ChunkPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if state.pending_shrink > 0 {
+            state.pending_shrink -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_jobs() {
+        assert_eq!("auto".parse::<Jobs>(), Ok(Jobs::Auto));
+        assert_eq!("8".parse::<Jobs>(), Ok(Jobs::Fixed(8)));
+        assert!("0".parse::<Jobs>().is_err());
+        assert!("many".parse::<Jobs>().is_err());
+
+        #[derive(Deserialize)]
+        struct Settings {
+            jobs: Jobs,
+        }
+        let settings: Settings = toml::from_str("jobs = 16").unwrap();
+        assert_eq!(settings.jobs, Jobs::Fixed(16));
+        let settings: Settings = toml::from_str("jobs = \"auto\"").unwrap();
+        assert_eq!(settings.jobs, Jobs::Auto);
+    }
+
+    #[test]
+    fn test_fixed_limit_never_changes() {
+        let limiter = ConcurrencyLimiter::new(Jobs::Fixed(2));
+        limiter.record(5, false);
+        for _ in 0..10 {
+            limiter.record(5, true);
+        }
+        assert_eq!(limiter.limit(), 2);
+        assert_eq!(limiter.max(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_backs_off_and_recovers() {
+        let limiter = ConcurrencyLimiter::new(Jobs::Auto);
+        assert_eq!(limiter.limit(), DEFAULT_JOBS);
+
+        // Two chunks are running when a rate limit comes in
+        let first = limiter.acquire().await;
+        let second = limiter.acquire().await;
+        limiter.record(1, true);
+        assert_eq!(limiter.limit(), DEFAULT_JOBS / 2);
+
+        // The same rate limit isn't counted twice
+        limiter.record(1, true);
+        assert_eq!(limiter.limit(), DEFAULT_JOBS / 2);
+
+        // Only as many chunks as the new limit can run once the busy ones finish
+        drop(first);
+        drop(second);
+        let _a = limiter.acquire().await;
+        let _b = limiter.acquire().await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), limiter.acquire())
+                .await
+                .is_err()
+        );
+
+        // A full round of successes raises the limit by one
+        limiter.record(1, true);
+        assert_eq!(limiter.limit(), DEFAULT_JOBS / 2 + 1);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), limiter.acquire())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_adaptive_limit_stays_within_bounds() {
+        let limiter = ConcurrencyLimiter::new(Jobs::Auto);
+        for seen in 1..10 {
+            limiter.record(seen, false);
+        }
+        assert_eq!(limiter.limit(), 1);
+
+        for _ in 0..1000 {
+            limiter.record(9, true);
+        }
+        assert_eq!(limiter.limit(), MAX_ADAPTIVE_JOBS);
+    }
+}
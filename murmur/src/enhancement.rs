@@ -0,0 +1,462 @@
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::transcription::join_text;
+
+/// Characters of the neighbouring pieces shown around a piece as context
+const CONTEXT_CHARS: usize = 300;
+
+/// Enhanced length accepted, as a fraction of the original length
+const LENGTH_RATIO: RangeInclusive<f64> = 0.5..=1.6;
+
+/// Texts shorter than this are not length-checked; "um, yes" may well become "Yes."
+const MIN_CHECKED_CHARS: usize = 200;
+
+/// A stretch of transcript enhanced in one request
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextPiece {
+    pub text: String,
+    /// Whether a paragraph break follows the piece in the transcript
+    pub ends_paragraph: bool,
+}
+
+/// Split `text` into pieces of at most `max_len` bytes. Pieces are cut between
+/// paragraphs where possible, otherwise between sentences, and only when a
+/// single sentence is too long between words.
+pub fn split_text(text: &str, max_len: usize) -> Vec<TextPiece> {
+    let mut pieces: Vec<TextPiece> = Vec::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        for sentence in sentences(paragraph) {
+            for part in split_long(sentence, max_len) {
+                if let Some(last) = pieces.last_mut() {
+                    let joined = if last.ends_paragraph {
+                        format!("{}\n\n{}", last.text, part)
+                    } else {
+                        join_text(&last.text, part)
+                    };
+                    if joined.len() <= max_len {
+                        *last = TextPiece {
+                            text: joined,
+                            ends_paragraph: false,
+                        };
+                        continue;
+                    }
+                }
+                pieces.push(TextPiece {
+                    text: part.to_string(),
+                    ends_paragraph: false,
+                });
+            }
+        }
+        if let Some(last) = pieces.last_mut() {
+            last.ends_paragraph = true;
+        }
+    }
+
+    pieces
+}
+
+/// Put enhanced pieces back together in the places of the original ones
+pub fn stitch(pieces: &[TextPiece], enhanced: &[String]) -> String {
+    let mut text = String::new();
+    let mut paragraph_break = false;
+    for (piece, enhanced) in pieces.iter().zip(enhanced) {
+        let enhanced = enhanced.trim();
+        text = if text.is_empty() {
+            enhanced.to_string()
+        } else if paragraph_break {
+            format!("{}\n\n{}", text, enhanced)
+        } else {
+            join_text(&text, enhanced)
+        };
+        paragraph_break = piece.ends_paragraph;
+    }
+    text
+}
+
+/// Prompt for enhancing piece `index`, with `render` filling the piece into the
+/// mode's template. A transcript that fits in one piece is sent as is; otherwise
+/// the end of the previous and the start of the next piece are shown so that all
+/// pieces can be enhanced at once and still read as one text.
+pub fn prompt(pieces: &[TextPiece], index: usize, render: impl Fn(&str) -> String) -> String {
+    let text = &pieces[index].text;
+    if pieces.len() == 1 {
+        return render(text);
+    }
+
+    let mut marked = String::new();
+    if let Some(previous) = index.checked_sub(1).map(|i| &pieces[i]) {
+        marked.push_str(&format!("<before>…{}</before>\n", tail(&previous.text)));
+    }
+    marked.push_str(&format!("<part>\n{}\n</part>", text));
+    if let Some(next) = pieces.get(index + 1) {
+        marked.push_str(&format!("\n<after>{}…</after>", head(&next.text)));
+    }
+
+    format!(
+        "The text below is part {} of {} of a longer transcript. Apply the instructions only to the text between <part> and </part>; the text around it is context. Output only the result for that part, without the tags.\n\n{}",
+        index + 1,
+        pieces.len(),
+        render(&marked)
+    )
+}
+
+/// Whether `enhanced` is about as long as `original`. Much shorter output was
+/// usually cut off at the token limit or summarized; much longer output has
+/// commentary mixed in.
+pub fn length_in_range(original: &str, enhanced: &str) -> bool {
+    let original_chars = original.chars().count();
+    if original_chars < MIN_CHECKED_CHARS {
+        return true;
+    }
+    let ratio = enhanced.chars().count() as f64 / original_chars as f64;
+    LENGTH_RATIO.contains(&ratio)
+}
+
+/// Prints enhanced pieces while they stream in. Pieces are enhanced
+/// concurrently, so only the earliest unfinished piece prints live; later ones
+/// are held back until it is their turn.
+pub struct StreamPrinter<W: Write> {
+    out: W,
+    pieces: Vec<StreamedPiece>,
+    /// The piece that prints live
+    current: usize,
+    /// Whether a status line is shown that the first output replaces
+    status_line: bool,
+    /// Whether a piece printed something other than what ended up in the text
+    diverged: bool,
+}
+
+#[derive(Default)]
+struct StreamedPiece {
+    held: String,
+    started: bool,
+    done: bool,
+    ends_paragraph: bool,
+}
+
+impl<W: Write> StreamPrinter<W> {
+    pub fn new(out: W, pieces: &[TextPiece], status_line: bool) -> Self {
+        Self {
+            out,
+            pieces: pieces
+                .iter()
+                .map(|piece| StreamedPiece {
+                    ends_paragraph: piece.ends_paragraph,
+                    ..StreamedPiece::default()
+                })
+                .collect(),
+            current: 0,
+            status_line,
+            diverged: false,
+        }
+    }
+
+    /// Text that arrived for piece `index`
+    pub fn push(&mut self, index: usize, delta: &str) {
+        if index == self.current {
+            self.write(index, delta);
+        } else {
+            self.pieces[index].held.push_str(delta);
+        }
+    }
+
+    /// Piece `index` is complete; print the held text of the pieces after it
+    pub fn finish(&mut self, index: usize) {
+        self.pieces[index].done = true;
+        while self
+            .pieces
+            .get(self.current)
+            .is_some_and(|piece| piece.done)
+        {
+            self.current += 1;
+            if let Some(next) = self.pieces.get_mut(self.current) {
+                let held = std::mem::take(&mut next.held);
+                self.write(self.current, &held);
+            }
+        }
+    }
+
+    /// Piece `index` keeps its original text instead of what was streamed. That
+    /// text is printed instead, unless part of the streamed text already was.
+    pub fn keep_original(&mut self, index: usize, original: &str) {
+        if self.pieces[index].started {
+            self.diverged = true;
+        } else {
+            self.pieces[index].held.clear();
+            self.push(index, original);
+        }
+    }
+
+    /// End the output. Returns `false` if what was printed differs from the
+    /// final text, which then has to be shown again.
+    pub fn end(mut self) -> bool {
+        if self.status_line {
+            write!(self.out, "\r\x1b[K").ok();
+        } else {
+            writeln!(self.out).ok();
+        }
+        self.out.flush().ok();
+        !self.diverged
+    }
+
+    fn write(&mut self, index: usize, text: &str) {
+        let piece = &self.pieces[index];
+        // Replies tend to start with whitespace, which would end up between pieces
+        let text = if piece.started {
+            text
+        } else {
+            text.trim_start()
+        };
+        if text.is_empty() {
+            return;
+        }
+
+        if self.status_line {
+            write!(self.out, "\r\x1b[K").ok();
+            self.status_line = false;
+        }
+        if !piece.started && index > 0 {
+            let separator = if self.pieces[index - 1].ends_paragraph {
+                "\n\n"
+            } else {
+                " "
+            };
+            write!(self.out, "{}", separator).ok();
+        }
+        self.pieces[index].started = true;
+        write!(self.out, "{}", text).ok();
+        self.out.flush().ok();
+    }
+}
+
+/// Sentences of a paragraph, each with its closing punctuation
+fn sentences(paragraph: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let ends_sentence = match c {
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            '。' | '！' | '？' => true,
+            _ => false,
+        };
+        if ends_sentence {
+            let end = i + c.len_utf8();
+            sentences.push(paragraph[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(paragraph[start..].trim());
+
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+/// Cut text that has no sentence ends into parts of at most `max_len` bytes,
+/// between words where there are any
+fn split_long(text: &str, max_len: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+
+    while rest.len() > max_len {
+        let mut cut = max_len;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        if !rest[cut..].starts_with(char::is_whitespace) {
+            if let Some(space) = rest[..cut].rfind(char::is_whitespace).filter(|&s| s > 0) {
+                cut = space;
+            }
+        }
+        if cut == 0 {
+            // A single character wider than `max_len`
+            cut = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        parts.push(rest[..cut].trim_end());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+
+    parts
+}
+
+/// The last `CONTEXT_CHARS` characters of `text`
+fn tail(text: &str) -> &str {
+    let start = text
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    &text[start..]
+}
+
+/// The first `CONTEXT_CHARS` characters of `text`
+fn head(text: &str) -> &str {
+    let end = text
+        .char_indices()
+        .nth(CONTEXT_CHARS)
+        .map_or(text.len(), |(i, _)| i);
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(pieces: &[TextPiece]) -> Vec<&str> {
+        pieces.iter().map(|piece| piece.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_split_text_prefers_paragraphs_then_sentences() {
+        let text = "First one. Second one.\n\nThird one here. Fourth!";
+
+        // Everything fits
+        let pieces = split_text(text, 100);
+        assert_eq!(texts(&pieces), vec![text]);
+        assert!(pieces[0].ends_paragraph);
+
+        // Whole paragraphs fit
+        let pieces = split_text(text, 30);
+        assert_eq!(
+            texts(&pieces),
+            vec!["First one. Second one.", "Third one here. Fourth!"]
+        );
+        assert!(pieces[0].ends_paragraph);
+
+        // Only sentences fit
+        let pieces = split_text(text, 16);
+        assert_eq!(
+            texts(&pieces),
+            vec!["First one.", "Second one.", "Third one here.", "Fourth!"]
+        );
+        assert_eq!(
+            pieces.iter().map(|p| p.ends_paragraph).collect::<Vec<_>>(),
+            vec![false, true, false, true]
+        );
+        assert!(pieces.iter().all(|piece| piece.text.len() <= 16));
+
+        // Decimals don't end sentences
+        assert_eq!(
+            sentences("Version 3.5 is out. Yes"),
+            vec!["Version 3.5 is out.", "Yes"]
+        );
+    }
+
+    #[test]
+    fn test_split_text_long_sentences() {
+        let pieces = split_text("one two three four five six", 10);
+        assert_eq!(texts(&pieces), vec!["one two", "three four", "five six"]);
+
+        // CJK has no spaces, but cuts stay on character boundaries
+        let pieces = split_text("今天天气很好我们去公园散步吧", 10);
+        assert!(pieces.iter().all(|piece| piece.text.len() <= 10));
+        assert_eq!(
+            stitch(
+                &pieces,
+                &texts(&pieces)
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+            ),
+            "今天天气很好我们去公园散步吧"
+        );
+
+        assert_eq!(split_text("  \n\n ", 10), vec![]);
+    }
+
+    #[test]
+    fn test_stitch_restores_layout() {
+        let text = "First one. Second one.\n\nThird one here. Fourth!";
+        let pieces = split_text(text, 16);
+        let enhanced: Vec<String> = pieces
+            .iter()
+            .map(|piece| piece.text.to_uppercase())
+            .collect();
+
+        assert_eq!(
+            stitch(&pieces, &enhanced),
+            "FIRST ONE. SECOND ONE.\n\nTHIRD ONE HERE. FOURTH!"
+        );
+    }
+
+    #[test]
+    fn test_prompt() {
+        let render = |text: &str| format!("Improve:\n\n{}", text);
+
+        let pieces = split_text("hello world", 100);
+        assert_eq!(prompt(&pieces, 0, render), "Improve:\n\nhello world");
+
+        let pieces = split_text("First one. Second one. Third one.", 12);
+        let middle = prompt(&pieces, 1, render);
+        assert!(middle.contains("part 2 of 3"));
+        assert!(middle.contains("<before>…First one.</before>"));
+        assert!(middle.contains("<part>\nSecond one.\n</part>"));
+        assert!(middle.contains("<after>Third one.…</after>"));
+        assert!(middle.contains("Improve:\n\n<before>"));
+        assert!(!prompt(&pieces, 0, render).contains("<before>"));
+        assert!(!prompt(&pieces, 2, render).contains("<after>"));
+
+        let long = "a".repeat(CONTEXT_CHARS * 2);
+        assert_eq!(tail(&long).len(), CONTEXT_CHARS);
+        assert_eq!(head(&long).len(), CONTEXT_CHARS);
+    }
+
+    #[test]
+    fn test_stream_printer_keeps_piece_order() {
+        let pieces = split_text("First one.\n\nSecond one. Third one.", 12);
+        assert_eq!(pieces.len(), 3);
+        let mut printer = StreamPrinter::new(Vec::new(), &pieces, true);
+
+        printer.push(1, "Second");
+        printer.push(0, " First");
+        printer.push(2, "Third one.");
+        printer.finish(2);
+        printer.push(0, " one.");
+        printer.push(1, " one.");
+        printer.finish(0);
+        printer.finish(1);
+
+        let out = String::from_utf8(printer.out.clone()).unwrap();
+        assert_eq!(out, "\r\x1b[KFirst one.\n\nSecond one. Third one.");
+        assert!(printer.end());
+    }
+
+    #[test]
+    fn test_stream_printer_reports_divergence() {
+        let pieces = split_text("First one. Second one.", 12);
+
+        let mut printer = StreamPrinter::new(Vec::new(), &pieces, false);
+        printer.push(0, "First.");
+        printer.keep_original(0, "First one.");
+        printer.finish(0);
+        assert!(!printer.end());
+
+        // A piece that is still held back is printed as it was transcribed
+        let mut printer = StreamPrinter::new(Vec::new(), &pieces, false);
+        printer.push(1, "Held back");
+        printer.keep_original(1, "Second one.");
+        printer.finish(1);
+        printer.push(0, "First one.");
+        printer.finish(0);
+        assert_eq!(printer.out, b"First one. Second one.");
+        assert!(printer.end());
+    }
+
+    #[test]
+    fn test_length_in_range() {
+        let original = "word ".repeat(100);
+        assert!(length_in_range(&original, &original));
+        assert!(length_in_range(&original, &original[..300]));
+        assert!(!length_in_range(&original, &original[..200]));
+        assert!(!length_in_range(&original, &original.repeat(2)));
+
+        // Short texts aren't checked
+        assert!(length_in_range("um, so, yes", "Yes."));
+    }
+}
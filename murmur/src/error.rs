@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Exit code for errors that don't fall in any [`MurmurError`] category
+pub const EXIT_FAILURE: u8 = 1;
+
+/// Failures that callers may want to handle differently.
+///
+/// These travel inside `anyhow::Error` like every other error in murmur, with
+/// context added on the way up; [`MurmurError::find`] gets them back out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MurmurError {
+    /// The API key is missing a permission or was rejected
+    Auth(String),
+    /// The API kept answering with rate limits after every retry
+    RateLimit(String),
+    /// The server couldn't be reached, timed out or kept failing
+    Network(String),
+    /// The input file is missing, unreadable or has no usable audio
+    UnsupportedInput(String),
+    /// FFmpeg isn't installed
+    ToolMissing(String),
+    /// No microphone could be opened
+    AudioDevice(String),
+    /// A cache entry or job manifest is damaged
+    CacheCorrupt(String),
+}
+
+impl MurmurError {
+    /// The typed error anywhere in an error chain
+    pub fn find(error: &anyhow::Error) -> Option<&MurmurError> {
+        error.downcast_ref()
+    }
+
+    /// Stable name of the category, used in JSON error output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MurmurError::Auth(_) => "auth",
+            MurmurError::RateLimit(_) => "rate_limit",
+            MurmurError::Network(_) => "network",
+            MurmurError::UnsupportedInput(_) => "unsupported_input",
+            MurmurError::ToolMissing(_) => "tool_missing",
+            MurmurError::AudioDevice(_) => "audio_device",
+            MurmurError::CacheCorrupt(_) => "cache_corrupt",
+        }
+    }
+
+    /// Process exit code. These are stable; 2 is left to clap for usage errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            MurmurError::Auth(_) => 3,
+            MurmurError::RateLimit(_) => 4,
+            MurmurError::Network(_) => 5,
+            MurmurError::UnsupportedInput(_) => 6,
+            MurmurError::ToolMissing(_) => 7,
+            MurmurError::AudioDevice(_) => 8,
+            MurmurError::CacheCorrupt(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for MurmurError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MurmurError::Auth(message)
+            | MurmurError::RateLimit(message)
+            | MurmurError::Network(message)
+            | MurmurError::UnsupportedInput(message)
+            | MurmurError::ToolMissing(message)
+            | MurmurError::AudioDevice(message)
+            | MurmurError::CacheCorrupt(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for MurmurError {}
+
+/// Exit code for any error
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    MurmurError::find(error).map_or(EXIT_FAILURE, MurmurError::exit_code)
+}
+
+/// Machine-readable form of an error, printed with `--json-errors`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ErrorReport {
+    /// Category from [`MurmurError::kind`], or `other`
+    pub kind: &'static str,
+    /// The error with all of its context
+    pub message: String,
+    pub exit_code: u8,
+}
+
+impl ErrorReport {
+    pub fn new(error: &anyhow::Error) -> Self {
+        Self {
+            kind: MurmurError::find(error).map_or("other", MurmurError::kind),
+            message: format!("{:#}", error),
+            exit_code: exit_code(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_found_through_context() {
+        let error = anyhow::Error::from(MurmurError::Auth("Invalid API key".to_string()))
+            .context("Failed to transcribe chunk 3")
+            .context("1 of 4 chunks failed");
+
+        assert_eq!(
+            MurmurError::find(&error),
+            Some(&MurmurError::Auth("Invalid API key".to_string()))
+        );
+        assert_eq!(exit_code(&error), 3);
+
+        // Also when used as context over a lower-level error
+        let io_error = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let error = Err::<(), _>(io_error)
+            .context(MurmurError::ToolMissing("ffmpeg not found".to_string()))
+            .unwrap_err();
+        assert_eq!(exit_code(&error), 7);
+    }
+
+    #[test]
+    fn test_error_report() {
+        let error = anyhow::Error::from(MurmurError::RateLimit("Slow down".to_string()))
+            .context("Transcription failed");
+        assert_eq!(
+            ErrorReport::new(&error),
+            ErrorReport {
+                kind: "rate_limit",
+                message: "Transcription failed: Slow down".to_string(),
+                exit_code: 4,
+            }
+        );
+
+        let report = ErrorReport::new(&anyhow::anyhow!("Something else"));
+        assert_eq!(report.kind, "other");
+        assert_eq!(report.exit_code, EXIT_FAILURE);
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"kind":"other","message":"Something else","exit_code":1}"#
+        );
+    }
+}
@@ -1,114 +1,573 @@
 //! Murmur - Audio transcription using OpenAI Whisper API
 //!
-//! This library provides functionality to transcribe audio files using OpenAI's Whisper API,
+//! This library provides functionality to transcribe audio and video files using OpenAI's Whisper API,
 //! with support for voice recording, large file chunking and caching.
-//! 
+//!
 //! # Usage
-//! 
-//! - File transcription: `murmur file.mp3`
+//!
+//! - File transcription: `murmur file.mp3` (any format FFmpeg can read)
 //! - Voice recording: `murmur` (no arguments)
 
-use anyhow::Result;
-use clap::Parser;
-use futures::stream::{self, StreamExt};
-use std::path::PathBuf;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::sync::{watch, Semaphore};
+
+use crate::enhancement::{StreamPrinter, TextPiece};
+use crate::utils::{ENHANCEMENT_JOBS, ENHANCEMENT_PIECE_BYTES, EXTRACTION_JOBS};
 
+pub mod backend;
 pub mod cache;
 pub mod chunking;
 pub mod client;
+pub mod concurrency;
+pub mod enhancement;
+pub mod error;
+pub mod media;
+#[cfg(feature = "native-audio")]
+pub mod native_audio;
+pub mod output;
+pub mod request;
+pub mod retry;
+pub mod settings;
+pub mod template;
 pub mod transcription;
 pub mod utils;
+pub mod vocabulary;
 pub mod voice_recorder;
 
 // Re-export commonly used items
-pub use cache::CacheManager;
+pub use backend::{BackendKind, TranscriptionBackend, WhisperCppClient};
+pub use cache::{CacheCommand, CacheManager, JobManifest, TranscriptionParams};
+pub use chunking::AudioChunk;
 pub use chunking::AudioChunker;
 pub use client::WhisperClient;
-pub use transcription::TranscriptMerger;
-pub use utils::{Config, FileCleanupHelper, FileMetadata, ProgressDisplay, StatusLineManager};
+pub use concurrency::{ConcurrencyLimiter, Jobs};
+pub use error::{ErrorReport, MurmurError};
+pub use media::{AudioEngine, MediaInfo, Preprocess};
+pub use output::{OutputFormat, ENHANCED_EXTENSION};
+pub use request::{InputSource, TranscriptionRequest, TranscriptionRequestBuilder};
+pub use retry::RetryPolicy;
+pub use settings::{ApiKeySource, Settings};
+pub use template::Template;
+pub use transcription::{ChunkTranscript, Segment, Transcript, TranscriptMerger, Transcription};
+pub use utils::{
+    ChunkRecord, ChunkStatus, Config, FileCleanupHelper, FileMetadata, ProgressDisplay,
+    StatusLineManager,
+};
+pub use vocabulary::Vocabulary;
 pub use voice_recorder::VoiceRecorder;
 
+/// The input file as given by the user; the audio that is transcribed may be
+/// extracted from it
+struct SourceFile<'a> {
+    path: &'a Path,
+    hash: &'a str,
+}
+
+/// Slots shared by the chunks of one file: a few chunks are cut from the
+/// source at a time, and uploads run as the concurrency limiter allows.
+/// Chained chunks are uploaded in order instead, each prompted with the end
+/// of the previous chunk's transcript.
+struct ChunkPipeline<'a> {
+    source: &'a Path,
+    extraction: Semaphore,
+    limiter: ConcurrencyLimiter,
+    /// Transcript of each chunk once it is done, when chunks are chained
+    handoffs: Vec<watch::Sender<Option<String>>>,
+}
+
+impl<'a> ChunkPipeline<'a> {
+    fn new(source: &'a Path, config: &Config, chunks: usize) -> Self {
+        let handoffs = match config.chain_chunks {
+            true => (0..chunks).map(|_| watch::channel(None).0).collect(),
+            false => Vec::new(),
+        };
+
+        Self {
+            source,
+            extraction: Semaphore::new(EXTRACTION_JOBS),
+            limiter: ConcurrencyLimiter::new(config.jobs),
+            handoffs,
+        }
+    }
+
+    /// Most chunks worth having in flight: enough to keep every upload slot
+    /// busy while the next chunks are cut
+    fn width(&self) -> usize {
+        self.limiter.max() + EXTRACTION_JOBS
+    }
+
+    /// Cut the chunk from the source, unless an earlier run already did
+    async fn extract(&self, chunker: &AudioChunker, chunk: &AudioChunk) -> Result<()> {
+        if Path::new(&chunk.path).exists() {
+            return Ok(());
+        }
+        let _permit = self.extraction.acquire().await?;
+        chunker.extract_chunk(self.source, chunk).await
+    }
+
+    /// Transcribe a chunk once an upload slot is free, adapting the limit to
+    /// how the backend coped
+    async fn transcribe(
+        &self,
+        backend: &dyn TranscriptionBackend,
+        chunk: &AudioChunk,
+        index: usize,
+        request: &TranscriptionRequest,
+    ) -> Result<Transcript> {
+        let request = self.chunk_request(index, request).await;
+        let _permit = self.limiter.acquire().await;
+        let result = backend.transcribe(Path::new(&chunk.path), &request).await;
+        self.limiter
+            .record(backend.rate_limit_count(), result.is_ok());
+        result
+    }
+
+    /// The request for a chunk. Chained chunks wait for the previous one and
+    /// continue from its transcript.
+    async fn chunk_request(
+        &self,
+        index: usize,
+        request: &TranscriptionRequest,
+    ) -> TranscriptionRequest {
+        let Some(previous) = index.checked_sub(1).and_then(|i| self.handoffs.get(i)) else {
+            return request.clone();
+        };
+        let previous_text = previous
+            .subscribe()
+            .wait_for(Option::is_some)
+            .await
+            .map(|text| text.clone().unwrap_or_default())
+            .unwrap_or_default();
+
+        TranscriptionRequest {
+            prompt: vocabulary::chunk_prompt(request.prompt.as_deref(), &previous_text),
+            ..request.clone()
+        }
+    }
+
+    /// Hand a finished chunk's transcript to the next chunk. A failed chunk
+    /// hands on nothing, so the next one goes ahead without it.
+    fn hand_off(&self, index: usize, transcript: Option<&Transcript>) {
+        if let Some(handoff) = self.handoffs.get(index) {
+            handoff.send_replace(Some(transcript.map(|t| t.text.clone()).unwrap_or_default()));
+        }
+    }
+}
+
+/// Subcommands besides transcription
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Inspect and clean up cached chunks, transcripts and enhancements
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Retry the failed chunks of an interrupted large-file job
+    Resume {
+        /// The file the job was started on
+        input: PathBuf,
+    },
+}
+
 /// Command line arguments for the Murmur audio transcription tool
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(name = "murmur")]
 #[command(
-    about = "Transcribe MP3 audio files using OpenAI Whisper API or record voice for transcription"
+    about = "Transcribe audio and video files using OpenAI Whisper API or record voice for transcription"
 )]
 pub struct Args {
-    /// Input MP3 file path. If not provided, enters voice recording mode
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Input audio or video file path. If not provided, enters voice recording mode
     pub input: Option<PathBuf>,
 
     /// Language code for transcription (e.g., 'en' for English, 'es' for Spanish)
     #[arg(short, long)]
     pub language: Option<String>,
+
+    /// Output format; srt, vtt and json include segment timestamps
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Transcription backend to use [default: openai]
+    #[arg(long, value_enum, env = "MURMUR_BACKEND")]
+    pub backend: Option<BackendKind>,
+
+    /// Base URL of the transcription server (required for openai-compatible)
+    #[arg(long, env = "MURMUR_BASE_URL")]
+    pub base_url: Option<String>,
+
+    /// Transcription model (e.g. 'whisper-1', 'gpt-4o-transcribe')
+    #[arg(long, env = "MURMUR_MODEL")]
+    pub model: Option<String>,
+
+    /// Chat model used for text enhancement
+    #[arg(long, env = "MURMUR_ENHANCE_MODEL")]
+    pub enhance_model: Option<String>,
+
+    /// Enhancement mode: clean, email, notes, commit, slack, summary or the name
+    /// of a template in the templates directory [default: clean]
+    #[arg(short, long, env = "MURMUR_MODE")]
+    pub mode: Option<String>,
+
+    /// Environment variable holding the API key [default: OPENAI_API_KEY]
+    #[arg(long, env = "MURMUR_API_KEY_ENV")]
+    pub api_key_env: Option<String>,
+
+    /// File containing the API key
+    #[arg(long, env = "MURMUR_API_KEY_FILE")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Shell command that prints the API key (e.g. 'pass show openai')
+    #[arg(long, env = "MURMUR_API_KEY_CMD")]
+    pub api_key_cmd: Option<String>,
+
+    /// Chunks to transcribe at once: a number, or 'auto' to adapt to rate limits
+    /// [default: auto, 1 for whisper-cpp]
+    #[arg(short, long, env = "MURMUR_JOBS")]
+    pub jobs: Option<Jobs>,
+
+    /// Prompt each chunk with the end of the previous chunk's transcript;
+    /// chunks are then transcribed one at a time [default: false]
+    #[arg(
+        long,
+        env = "MURMUR_CHAIN_CHUNKS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        overrides_with = "no_chain_chunks"
+    )]
+    pub chain_chunks: Option<bool>,
+
+    /// Transcribe chunks independently, as many at a time as --jobs allows
+    #[arg(long, overrides_with = "chain_chunks")]
+    pub no_chain_chunks: bool,
+
+    /// Attempts per API request before giving up on rate limits and server errors [default: 4]
+    #[arg(long, env = "MURMUR_MAX_ATTEMPTS")]
+    pub max_attempts: Option<u32>,
+
+    /// Shrink audio before upload: mono, 16 kHz, low-bitrate MP3 or Opus [default: none]
+    #[arg(long, value_enum, env = "MURMUR_PREPROCESS")]
+    pub preprocess: Option<Preprocess>,
+
+    /// Bitrate in kbps for --preprocess [default: 32]
+    #[arg(long, env = "MURMUR_PREPROCESS_BITRATE")]
+    pub preprocess_bitrate: Option<u32>,
+
+    /// Enhance the transcript with the --mode template [default: only when recording]
+    #[arg(long, overrides_with = "no_enhance")]
+    pub enhance: bool,
+
+    /// Keep the raw transcript without enhancing it
+    #[arg(long, overrides_with = "enhance")]
+    pub no_enhance: bool,
+
+    /// Ignore cached transcripts and enhancements and ask the API again
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Print errors to stderr as a JSON object with a category and exit code
+    #[arg(long, global = true)]
+    pub json_errors: bool,
+
+    /// Config file path [default: ~/.config/murmur/config.toml]
+    #[arg(long, env = "MURMUR_CONFIG")]
+    pub config: Option<PathBuf>,
 }
 
 /// Main transcription orchestrator that handles both file processing and voice recording
 pub struct MurmurProcessor {
     config: Config,
+    backend: Box<dyn TranscriptionBackend>,
     client: WhisperClient,
     cache_manager: CacheManager,
     chunker: AudioChunker,
     merger: TranscriptMerger,
+    template: Template,
 }
 
 impl MurmurProcessor {
     pub fn new(api_key: String) -> Result<Self> {
-        let config = Config::default();
-        let client = WhisperClient::new(api_key, &config)?;
+        Self::with_config(api_key, Config::default())
+    }
+
+    pub fn with_config(api_key: String, config: Config) -> Result<Self> {
+        let backend = backend::create_backend(&api_key, &config)?;
+        // Enhancement goes to the transcription server when it speaks the OpenAI API
+        let client = match (config.backend, &config.base_url) {
+            (BackendKind::WhisperCpp, _) | (_, None) => WhisperClient::new(api_key, &config)?,
+            (_, Some(base_url)) => {
+                WhisperClient::with_base_url(api_key, base_url.clone(), &config)?
+            }
+        };
         let cache_manager = CacheManager::new(&config);
         let chunker = AudioChunker::new(&config);
         let merger = TranscriptMerger::new();
+        // Load the template up front, so a typo doesn't cost a recording
+        let template = Template::load(&config.enhancement_mode, config.templates_dir.as_deref())?;
 
         Ok(Self {
             config,
+            backend,
             client,
             cache_manager,
             chunker,
             merger,
+            template,
         })
     }
 
-    pub async fn process(&self, args: &Args) -> Result<String> {
-        match &args.input {
-            Some(input_path) => {
-                // File mode - process existing audio file
-                utils::validate_input_file(input_path).await?;
+    /// Transcribe the request's input, then enhance the transcript if asked to
+    pub async fn process(&self, request: &TranscriptionRequest) -> Result<Transcription> {
+        let enhance = self.should_enhance(request)?;
+        let transcript = match request.input_path() {
+            Some(input_path) => self.process_file(request, input_path).await?,
+            None => self.process_recording_mode(request).await?,
+        };
 
-                let file_size = utils::get_file_size(input_path).await?;
+        // Recordings are printed in a single format, and enhanced text has no timestamps
+        let timed_recording = request.input_path().is_none() && request.format.needs_segments();
+        if timed_recording && request.enhance == Some(true) {
+            println!("Enhanced text has no timestamps; printing the raw transcript");
+        }
+        if !enhance || timed_recording {
+            return Ok(Transcription {
+                transcript,
+                enhanced: None,
+            });
+        }
 
-                if file_size <= self.config.max_file_size_bytes() {
-                    // Small file - process directly
-                    self.process_small_file(args).await
-                } else {
-                    // Large file - use chunking strategy
-                    self.process_large_file(args).await
-                }
+        let enhanced = self.enhance_or_keep_raw(&transcript.text, request).await;
+        Ok(Transcription {
+            transcript,
+            enhanced,
+        })
+    }
+
+    /// Enhance a finished transcript. A failed enhancement only costs the
+    /// enhanced text: the raw transcript is still saved or printed.
+    async fn enhance_or_keep_raw(
+        &self,
+        text: &str,
+        request: &TranscriptionRequest,
+    ) -> Option<String> {
+        // Show status while waiting for OpenAI enhancement. Recordings print the
+        // enhanced text as it streams in, which replaces the status line.
+        let live = request.input_path().is_none();
+        StatusLineManager::show_status("Waiting for OpenAI response...");
+        let enhanced = self.enhance_transcription(text, request, live).await;
+        if !live {
+            StatusLineManager::clear_status();
+        }
+
+        match enhanced {
+            Ok(enhanced) => Some(enhanced),
+            Err(e) => {
+                println!("\nEnhancement failed, keeping the raw transcript: {:#}", e);
+                None
             }
-            None => {
-                // Recording mode - record audio once and transcribe
-                self.process_recording_mode(args).await
+        }
+    }
+
+    /// Whether to enhance the request's transcript. whisper-cpp transcribes
+    /// without an API key, but enhancement needs one: asking for it fails
+    /// before anything is transcribed, and recordings are left as they are.
+    fn should_enhance(&self, request: &TranscriptionRequest) -> Result<bool> {
+        if !request.enhances() || self.client.has_api_key() {
+            return Ok(request.enhances());
+        }
+        if request.enhance == Some(true) {
+            return Err(MurmurError::Auth(
+                "Enhancement needs an OpenAI API key; set one up or drop --enhance".to_string(),
+            )
+            .into());
+        }
+
+        println!("No API key for enhancement; keeping the raw transcript");
+        Ok(false)
+    }
+
+    /// File mode - transcribe an existing audio or video file
+    async fn process_file(
+        &self,
+        request: &TranscriptionRequest,
+        input_path: &Path,
+    ) -> Result<Transcript> {
+        let media_info = utils::validate_input_file(input_path).await?;
+
+        // Transcripts are cached by file content, so renamed or copied files hit too
+        let file_hash = utils::calculate_file_hash(input_path).await?;
+        let params_key = TranscriptionParams::new(&self.config, request).cache_key();
+        if request.use_cache {
+            if let Some(cached) = self
+                .cache_manager
+                .get_file_transcript(&file_hash, &params_key)
+                .await?
+            {
+                println!("Using cached transcript");
+                return Ok(cached);
             }
         }
+
+        let source = SourceFile {
+            path: input_path,
+            hash: &file_hash,
+        };
+        let job_dir = self.cache_manager.job_dir(&file_hash);
+        let resumable = match self.cache_manager.resumable_job(&job_dir, &file_hash).await {
+            Ok(job) => job.is_some(),
+            // The damaged manifest has been removed, so the job starts over
+            Err(e) => {
+                println!("{:#}", e);
+                false
+            }
+        };
+        let transcript = if resumable {
+            // An earlier run already planned the chunks; any that are missing
+            // are cut straight from the input
+            self.process_large_file(request, input_path, &source)
+                .await?
+        } else if media_info.needs_extraction(input_path) || self.config.preprocess.is_enabled() {
+            self.process_transcoded_audio(request, &source).await?
+        } else {
+            self.process_audio_file(request, input_path, &source)
+                .await?
+        };
+
+        self.cache_manager
+            .save_file_transcript(input_path, &file_hash, &params_key, &transcript)
+            .await?;
+        Ok(transcript)
+    }
+
+    /// Request for continuing the unfinished job on `input`, with the language and
+    /// output format it was started with
+    pub async fn resume_request(
+        &self,
+        request: &TranscriptionRequest,
+        input: &Path,
+    ) -> Result<TranscriptionRequest> {
+        if !input.exists() {
+            return Err(MurmurError::UnsupportedInput(format!(
+                "Input file {:?} does not exist",
+                input
+            ))
+            .into());
+        }
+
+        let file_hash = utils::calculate_file_hash(input).await?;
+        let job_dir = self.cache_manager.job_dir(&file_hash);
+        let manifest = self
+            .cache_manager
+            .resumable_job(&job_dir, &file_hash)
+            .await?
+            .with_context(|| {
+                format!(
+                    "No unfinished job for {:?}. Start one with `murmur {}`",
+                    input,
+                    input.display()
+                )
+            })?;
+
+        Ok(TranscriptionRequest {
+            source: InputSource::File(input.to_path_buf()),
+            language: manifest.language,
+            format: manifest.format,
+            ..request.clone()
+        })
+    }
+
+    async fn process_audio_file(
+        &self,
+        request: &TranscriptionRequest,
+        audio_path: &Path,
+        source: &SourceFile<'_>,
+    ) -> Result<Transcript> {
+        let file_size = utils::get_file_size(audio_path).await?;
+
+        if file_size <= self.config.max_file_size_bytes() {
+            // Small file - process directly
+            self.backend.transcribe(audio_path, request).await
+        } else {
+            // Large file - use chunking strategy
+            self.process_large_file(request, audio_path, source).await
+        }
+    }
+
+    /// Transcribe audio that first needs re-encoding: the audio track of a video, a
+    /// container the API doesn't accept, or any input when pre-processing is enabled
+    async fn process_transcoded_audio(
+        &self,
+        request: &TranscriptionRequest,
+        source: &SourceFile<'_>,
+    ) -> Result<Transcript> {
+        if self.config.preprocess.is_enabled() {
+            println!("Pre-processing audio...");
+        } else {
+            println!("Extracting audio...");
+        }
+        let audio_file = self.transcode_to_temp_file(source.path).await?;
+
+        let result = self.process_audio_file(request, &audio_file, source).await;
+
+        FileCleanupHelper::cleanup_file(&audio_file).await?;
+        result
     }
 
-    async fn process_recording_mode(&self, args: &Args) -> Result<String> {
+    /// Re-encode audio into a temporary file using the configured pre-processing
+    async fn transcode_to_temp_file(&self, input_path: &Path) -> Result<PathBuf> {
+        let audio_file = std::env::temp_dir().join(format!(
+            "murmur_audio_{}.{}",
+            std::process::id(),
+            AudioEngine::detect().extension(self.config.preprocess)
+        ));
+
+        if let Err(e) = media::transcode_audio(
+            input_path,
+            &audio_file,
+            self.config.preprocess,
+            self.config.preprocess_bitrate_kbps,
+        )
+        .await
+        {
+            FileCleanupHelper::cleanup_file(&audio_file).await.ok();
+            return Err(e);
+        }
+
+        Ok(audio_file)
+    }
+
+    async fn process_recording_mode(&self, request: &TranscriptionRequest) -> Result<Transcript> {
         println!("Recording mode: recording audio.");
         println!("Press 'q' to stop recording and transcribe.");
         println!();
 
         // Process voice recording directly - single recording session
-        self.process_recording_session(args).await
+        self.process_recording_session(request).await
     }
 
-    async fn process_recording_session(&self, args: &Args) -> Result<String> {
+    async fn process_recording_session(
+        &self,
+        request: &TranscriptionRequest,
+    ) -> Result<Transcript> {
         // Record audio using direct recording method
-        let audio_file = VoiceRecorder::record_directly().await?;
-        
-        // Create temporary args with the recorded file
-        let mut temp_args = args.clone();
-        temp_args.input = Some(audio_file.clone());
+        let mut audio_file = VoiceRecorder::record_directly().await?;
+
+        // Raw recordings are large uncompressed WAV, so shrink them when asked to
+        if self.config.preprocess.is_enabled() {
+            let recording = audio_file;
+            let transcoded = self.transcode_to_temp_file(&recording).await;
+            FileCleanupHelper::cleanup_file(&recording).await?;
+            audio_file = transcoded?;
+        }
 
         // Show status while waiting for Whisper API
         StatusLineManager::show_status("Waiting for Whisper response...");
@@ -117,9 +576,10 @@ impl MurmurProcessor {
         let transcription = {
             let file_size = utils::get_file_size(&audio_file).await?;
             if file_size <= self.config.max_file_size_bytes() {
-                self.client.transcribe(&temp_args).await?
+                self.backend.transcribe(&audio_file, request).await?
             } else {
-                self.process_large_file_transcription(&temp_args).await?
+                self.process_large_file_transcription(request, &audio_file)
+                    .await?
             }
         };
 
@@ -129,173 +589,349 @@ impl MurmurProcessor {
         // Clean up temporary audio file
         FileCleanupHelper::cleanup_file(&audio_file).await?;
 
-        // Show status while waiting for OpenAI enhancement
-        StatusLineManager::show_status("Waiting for OpenAI response...");
+        Ok(transcription)
+    }
 
-        // Enhance the transcription using OpenAI
-        let result = self.enhance_transcription(&transcription).await?;
+    /// Enhance the transcript with the mode's template. Modes that rewrite the
+    /// transcript work in pieces small enough for the model's reply limit, which
+    /// are enhanced concurrently and stitched back together in order. With
+    /// `live`, the text is printed as it streams in.
+    async fn enhance_transcription(
+        &self,
+        text: &str,
+        request: &TranscriptionRequest,
+        live: bool,
+    ) -> Result<String> {
+        let pieces = if self.template.rewrites {
+            enhancement::split_text(text, ENHANCEMENT_PIECE_BYTES)
+        } else {
+            enhancement::split_text(text, usize::MAX)
+        };
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let render = |transcript: &str| {
+            self.template
+                .render(transcript, request.language.as_deref(), &date)
+        };
 
-        // Clear the status line
-        StatusLineManager::clear_status();
+        let printer =
+            live.then(|| Mutex::new(StreamPrinter::new(std::io::stdout(), &pieces, true)));
+
+        let enhanced: Result<Vec<String>> = stream::iter(0..pieces.len())
+            .map(|i| self.enhance_piece(&pieces, i, &render, request.use_cache, printer.as_ref()))
+            .buffered(ENHANCEMENT_JOBS)
+            .try_collect()
+            .await;
+        let printed_in_full = printer.is_none_or(|printer| printer.into_inner().unwrap().end());
 
-        Ok(result)
+        let enhanced = enhancement::stitch(&pieces, &enhanced?);
+        if !printed_in_full {
+            println!(
+                "\nSome parts came back much shorter or longer and were kept as transcribed:\n\n{}",
+                enhanced
+            );
+        }
+        Ok(enhanced)
     }
 
-    async fn enhance_transcription(&self, text: &str) -> Result<String> {
-        let prompt = format!(
-            "Please improve and format the following transcribed text. Fix any grammar issues, make it coherent, add proper punctuation, and make it more readable while preserving the original meaning. Output only the improved text without any explanations:\n\n{}",
-            text
-        );
+    /// Enhance one piece, streaming it to `printer` if there is one. When
+    /// rewriting, the original text is kept if the result is far longer or
+    /// shorter than the piece.
+    async fn enhance_piece(
+        &self,
+        pieces: &[TextPiece],
+        index: usize,
+        render: impl Fn(&str) -> String,
+        use_cache: bool,
+        printer: Option<&Mutex<StreamPrinter<std::io::Stdout>>>,
+    ) -> Result<String> {
+        let print = |delta: &str| {
+            if let Some(printer) = printer {
+                printer.lock().unwrap().push(index, delta);
+            }
+        };
+        let finish = || {
+            if let Some(printer) = printer {
+                printer.lock().unwrap().finish(index);
+            }
+        };
 
-        let enhanced_text = self.client.enhance_text(&prompt).await?;
+        let prompt = enhancement::prompt(pieces, index, render);
+        let model = &self.config.enhancement_model;
+        let base_url = self.client.base_url();
+        if use_cache {
+            if let Some(cached) = self
+                .cache_manager
+                .get_enhanced_text(base_url, model, &prompt)
+                .await
+            {
+                print(&cached);
+                finish();
+                return Ok(cached);
+            }
+        }
+
+        let original = &pieces[index].text;
+        let enhanced_text = self.client.enhance_text_streaming(&prompt, print).await?;
+        if self.template.rewrites && !enhancement::length_in_range(original, &enhanced_text) {
+            match printer {
+                Some(printer) => printer.lock().unwrap().keep_original(index, original),
+                None => println!(
+                    "\rEnhancement of part {} of {} came back with {} characters for {}; keeping the original text",
+                    index + 1,
+                    pieces.len(),
+                    enhanced_text.chars().count(),
+                    original.chars().count()
+                ),
+            }
+            finish();
+            return Ok(original.clone());
+        }
+
+        self.cache_manager
+            .save_enhanced_text(base_url, model, &prompt, &enhanced_text)
+            .await?;
+        finish();
         Ok(enhanced_text)
     }
 
-    async fn process_small_file(&self, args: &Args) -> Result<String> {
-        self.client.transcribe(args).await
-    }
+    /// Transcribe a large file in chunks kept in its cache directory, resuming an
+    /// earlier job on the same file when there is one. `file_path` is the source
+    /// itself or audio transcoded from it.
+    async fn process_large_file(
+        &self,
+        request: &TranscriptionRequest,
+        file_path: &Path,
+        source: &SourceFile<'_>,
+    ) -> Result<Transcript> {
+        let file_size_mb = utils::bytes_to_mb(utils::get_file_size(file_path).await?);
+        println!("Processing large file ({:.1} MB)...", file_size_mb);
 
-    async fn process_large_file(&self, args: &Args) -> Result<String> {
-        self.process_large_file_internal(args, true).await
-    }
+        // Each input gets its own cache directory, locked while we work in it
+        let job_dir = self.cache_manager.job_dir(source.hash);
+        let _lock = self.cache_manager.lock(&job_dir).await?;
+
+        let metadata = match self
+            .cache_manager
+            .resumable_job(&job_dir, source.hash)
+            .await?
+        {
+            Some(metadata) => {
+                println!(
+                    "Resuming: {} of {} chunks already transcribed",
+                    metadata.completed_chunks(),
+                    metadata.chunk_count
+                );
+                metadata
+            }
+            None => {
+                // Drop whatever an earlier, incomplete split left behind
+                self.cache_manager.cleanup_job(&job_dir).await?;
+                let chunks = self.chunker.plan(file_path, &job_dir).await?;
+                self.cache_manager
+                    .create_metadata_file(&job_dir, source.path, source.hash, &chunks, request)
+                    .await?
+            }
+        };
 
-    async fn process_large_file_transcription(&self, args: &Args) -> Result<String> {
-        self.process_large_file_internal(args, false).await
+        let manifest = self.cache_manager.open_manifest(&job_dir, metadata);
+        let transcript = match self
+            .process_chunks_with_cache(request, file_path, &manifest)
+            .await
+        {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                println!(
+                    "Progress is saved; run `murmur resume {}` to retry the failed chunks",
+                    source.path.display()
+                );
+                return Err(e);
+            }
+        };
+        self.cache_manager.cleanup_job(&job_dir).await?;
+        Ok(transcript)
     }
 
-    async fn process_large_file_internal(&self, args: &Args, use_cache: bool) -> Result<String> {
-        let file_path = args.input.as_ref().unwrap();
+    /// Transcribe a large recording in chunks that are not cached
+    async fn process_large_file_transcription(
+        &self,
+        request: &TranscriptionRequest,
+        file_path: &Path,
+    ) -> Result<Transcript> {
+        let segment_dir = self.config.temp_dir_path();
+        let chunks = self.chunker.plan(file_path, &segment_dir).await?;
+        let transcript = self
+            .process_chunks_without_cache(request, file_path, chunks)
+            .await;
+        tokio::fs::remove_dir(&segment_dir).await.ok();
+        transcript
+    }
 
-        if use_cache {
-            let file_size_mb = utils::bytes_to_mb(utils::get_file_size(file_path).await?);
-            println!("Processing large file ({:.1} MB)...", file_size_mb);
-
-            // Calculate file hash and handle cache validation
-            let file_hash = utils::calculate_file_hash(file_path).await?;
-            self.cache_manager
-                .validate_and_cleanup_if_needed(&file_hash)
-                .await?;
-
-            // Create metadata file after splitting
-            let chunks = self.chunker.split_audio_file(file_path).await?;
-            self.cache_manager
-                .create_metadata_file(
-                    file_path,
-                    utils::get_file_size(file_path).await?,
-                    &file_hash,
-                    chunks.len(),
-                )
-                .await?;
+    async fn process_chunks_with_cache(
+        &self,
+        request: &TranscriptionRequest,
+        source: &Path,
+        manifest: &JobManifest,
+    ) -> Result<Transcript> {
+        let chunks: Vec<AudioChunk> = manifest
+            .chunks()
+            .await
+            .into_iter()
+            .map(|chunk| AudioChunk {
+                path: chunk.path,
+                start: chunk.start,
+                end: chunk.end,
+            })
+            .collect();
+        let params_key = TranscriptionParams::new(&self.config, request).cache_key();
+        let pipeline = ChunkPipeline::new(source, &self.config, chunks.len());
 
-            self.process_chunks_with_cache(args, chunks).await
-        } else {
-            // For temporary recordings - no cache, direct processing
-            let chunks = self.chunker.split_audio_file(file_path).await?;
-            self.process_chunks_without_cache(args, chunks).await
-        }
+        self.transcribe_chunks(&chunks, &pipeline, |i| {
+            let chunk = &chunks[i];
+            let params_key = &params_key;
+            let pipeline = &pipeline;
+            async move {
+                self.process_chunk(request, chunk, params_key, i, pipeline, manifest)
+                    .await
+            }
+        })
+        .await
     }
 
-    async fn process_chunks_with_cache(&self, args: &Args, chunks: Vec<String>) -> Result<String> {
-        let total_chunks = chunks.len();
-        let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    async fn process_chunks_without_cache(
+        &self,
+        request: &TranscriptionRequest,
+        source: &Path,
+        chunks: Vec<AudioChunk>,
+    ) -> Result<Transcript> {
+        let pipeline = ChunkPipeline::new(source, &self.config, chunks.len());
 
-        // Process chunks in parallel with concurrency limit
-        let results: Vec<Result<(usize, String)>> = stream::iter(chunks.into_iter().enumerate())
-            .map(|(i, chunk_path)| {
-                let args = args.clone();
-                let completed = Arc::clone(&completed);
+        let result = self
+            .transcribe_chunks(&chunks, &pipeline, |i| {
+                let chunk = &chunks[i];
+                let pipeline = &pipeline;
                 async move {
-                    let text = self.process_chunk(&args, &chunk_path, i).await?;
-                    let done = completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
-                    ProgressDisplay::show_parallel_progress(done, total_chunks);
-                    Ok((i, text))
+                    pipeline.extract(&self.chunker, chunk).await?;
+                    pipeline
+                        .transcribe(self.backend.as_ref(), chunk, i, request)
+                        .await
                 }
             })
-            .buffer_unordered(4) // Process up to 4 chunks concurrently
-            .collect()
             .await;
 
-        ProgressDisplay::clear_progress();
-
-        // Sort results by chunk index and collect transcripts
-        let mut indexed_transcripts: Vec<(usize, String)> = results
-            .into_iter()
-            .collect::<Result<Vec<_>>>()?;
-        indexed_transcripts.sort_by_key(|(i, _)| *i);
-        let transcripts: Vec<String> = indexed_transcripts.into_iter().map(|(_, t)| t).collect();
+        // Clean up temporary chunk files
+        let chunk_paths: Vec<PathBuf> = chunks
+            .iter()
+            .map(|chunk| PathBuf::from(&chunk.path))
+            .collect();
+        FileCleanupHelper::cleanup_files(&chunk_paths).await?;
 
-        self.cache_manager.cleanup_temp_files().await?;
-        Ok(self.merger.merge_transcripts(transcripts))
+        result
     }
 
-    async fn process_chunks_without_cache(
+    /// Run every chunk through the pipeline and merge the transcripts in order.
+    /// Chunks are started in order, so the first ones are uploaded while later
+    /// ones are still being cut.
+    async fn transcribe_chunks<F, Fut>(
         &self,
-        args: &Args,
-        chunks: Vec<String>,
-    ) -> Result<String> {
+        chunks: &[AudioChunk],
+        pipeline: &ChunkPipeline<'_>,
+        transcribe_chunk: F,
+    ) -> Result<Transcript>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = Result<Transcript>>,
+    {
         let total_chunks = chunks.len();
-        let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
-        let chunk_paths_for_cleanup: Vec<std::path::PathBuf> =
-            chunks.iter().map(std::path::PathBuf::from).collect();
-
-        // Process chunks in parallel with concurrency limit
-        let results: Vec<Result<(usize, String)>> = stream::iter(chunks.into_iter().enumerate())
-            .map(|(i, chunk_path)| {
-                let args = args.clone();
-                let completed = Arc::clone(&completed);
+        let completed = AtomicUsize::new(0);
+
+        let results: Vec<(usize, Result<Transcript>)> = stream::iter(0..total_chunks)
+            .map(|i| {
+                let chunk = transcribe_chunk(i);
+                let completed = &completed;
                 async move {
-                    let mut chunk_args = args.clone();
-                    chunk_args.input = Some(std::path::PathBuf::from(&chunk_path));
-                    let text = self.client.transcribe(&chunk_args).await?;
-                    let done = completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    let result = chunk.await;
+                    pipeline.hand_off(i, result.as_ref().ok());
+
+                    let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
                     ProgressDisplay::show_parallel_progress(done, total_chunks);
-                    Ok((i, text))
+                    (i, result)
                 }
             })
-            .buffer_unordered(4) // Process up to 4 chunks concurrently
+            // The pipeline's slots decide how many of these actually run
+            .buffer_unordered(pipeline.width())
             .collect()
             .await;
 
         ProgressDisplay::clear_progress();
 
-        // Sort results by chunk index and collect transcripts
-        let mut indexed_transcripts: Vec<(usize, String)> = results
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+        if failed > 0 {
+            let error = results
+                .into_iter()
+                .find_map(|(_, result)| result.err())
+                .unwrap();
+            return Err(error.context(format!("{} of {} chunks failed", failed, total_chunks)));
+        }
+
+        // Sort results by chunk index and pair transcripts with their chunk time ranges
+        let mut indexed_transcripts: Vec<(usize, Transcript)> = results
             .into_iter()
+            .map(|(i, result)| result.map(|transcript| (i, transcript)))
             .collect::<Result<Vec<_>>>()?;
         indexed_transcripts.sort_by_key(|(i, _)| *i);
-        let transcripts: Vec<String> = indexed_transcripts.into_iter().map(|(_, t)| t).collect();
-
-        // Clean up temporary chunk files
-        FileCleanupHelper::cleanup_files(&chunk_paths_for_cleanup).await?;
+        let transcripts: Vec<ChunkTranscript> = indexed_transcripts
+            .into_iter()
+            .map(|(i, transcript)| ChunkTranscript {
+                start: chunks[i].start,
+                end: chunks[i].end,
+                transcript,
+            })
+            .collect();
 
-        Ok(self.merger.merge_transcripts(transcripts))
+        Ok(self.merger.merge(transcripts))
     }
 
     async fn process_chunk(
         &self,
-        args: &Args,
-        chunk_path: &str,
+        request: &TranscriptionRequest,
+        chunk: &AudioChunk,
+        params_key: &str,
         chunk_index: usize,
-    ) -> Result<String> {
+        pipeline: &ChunkPipeline<'_>,
+        manifest: &JobManifest,
+    ) -> Result<Transcript> {
+        let chunk_path = chunk.path.as_str();
+
         // Check cache first
-        if let Some(cached_text) = self.cache_manager.get_cached_transcript(chunk_path).await? {
-            return Ok(cached_text);
+        if let Some(cached) = self
+            .cache_manager
+            .get_cached_transcript(chunk_path, params_key)
+            .await?
+        {
+            return Ok(cached);
         }
 
         // Process chunk with API
-        let mut chunk_args = args.clone();
-        chunk_args.input = Some(PathBuf::from(chunk_path));
+        let result = async {
+            pipeline.extract(&self.chunker, chunk).await?;
+            pipeline
+                .transcribe(self.backend.as_ref(), chunk, chunk_index, request)
+                .await
+        }
+        .await;
 
-        match self.client.transcribe(&chunk_args).await {
-            Ok(text) => {
+        match result {
+            Ok(transcript) => {
                 // Cache the result
                 self.cache_manager
-                    .save_transcript_cache(chunk_path, &text)
+                    .save_transcript_cache(chunk_path, params_key, &transcript)
                     .await?;
-                Ok(text)
+                manifest.record_success(chunk_index).await?;
+                Ok(transcript)
             }
             Err(e) => {
                 println!("\rError processing chunk {}: {}", chunk_index + 1, e);
+                manifest.record_failure(chunk_index, &e).await?;
                 Err(e)
             }
         }
@@ -305,26 +941,46 @@ impl MurmurProcessor {
         &self,
         input_path: &std::path::Path,
         content: &str,
+        format: OutputFormat,
     ) -> Result<PathBuf> {
-        utils::save_transcription(input_path, content).await
+        utils::save_transcription(input_path, content, format.extension()).await
     }
 
-    /// Handle output based on the mode and arguments
-    pub async fn handle_output(&self, args: &Args, transcription: &str) -> Result<()> {
-        match &args.input {
+    /// Handle output based on the mode and request
+    pub async fn handle_output(
+        &self,
+        request: &TranscriptionRequest,
+        transcription: &Transcription,
+    ) -> Result<()> {
+        match request.input_path() {
             Some(input_path) => {
-                // File mode - save to file
-                let output_path = self.save_transcription(input_path, transcription).await?;
-                println!(
-                    "Processing complete: {:?}",
-                    output_path.file_name().unwrap_or_default()
-                );
+                // File mode - save the raw transcript, and the enhanced text next to it
+                let rendered = request.format.render(&transcription.transcript)?;
+                let mut output_paths = vec![
+                    self.save_transcription(input_path, &rendered, request.format)
+                        .await?,
+                ];
+                if let Some(enhanced) = &transcription.enhanced {
+                    output_paths.push(
+                        utils::save_transcription(input_path, enhanced, ENHANCED_EXTENSION).await?,
+                    );
+                }
+
+                let names: Vec<String> = output_paths
+                    .iter()
+                    .map(|path| format!("{:?}", path.file_name().unwrap_or_default()))
+                    .collect();
+                println!("Processing complete: {}", names.join(", "));
                 Ok(())
             }
             None => {
-                // Recording mode - output to stdout
-                print!("\r");
-                println!("{}", transcription);
+                // Recording mode - output to stdout; enhanced text was printed while
+                // it streamed in
+                if transcription.enhanced.is_none() {
+                    let output = request.format.render(&transcription.transcript)?;
+                    print!("\r");
+                    println!("{}", output);
+                }
                 Ok(())
             }
         }
@@ -341,17 +997,400 @@ mod tests {
         let args = Args {
             input: None,
             language: None,
+            ..Args::default()
         };
 
         assert_eq!(args.input, None);
         assert_eq!(args.language, None);
     }
 
+    #[test]
+    fn test_parse_subcommands() {
+        let args =
+            Args::try_parse_from(["murmur", "cache", "prune", "--older-than", "30d"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Cache {
+                action: CacheCommand::Prune {
+                    older_than: Some(std::time::Duration::from_secs(30 * 86400)),
+                    max_size: None,
+                }
+            })
+        );
+        assert!(Args::try_parse_from(["murmur", "cache", "prune"]).is_err());
+
+        let args = Args::try_parse_from(["murmur", "resume", "talk.mp3"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Resume {
+                input: PathBuf::from("talk.mp3")
+            })
+        );
+
+        // Anything else is still an input file
+        let args = Args::try_parse_from(["murmur", "talk.mp3"]).unwrap();
+        assert_eq!(args.command, None);
+        assert_eq!(args.input, Some(PathBuf::from("talk.mp3")));
+    }
+
+    /// Transcribes each chunk as its file name and remembers the prompts it got
+    #[derive(Default)]
+    struct PromptRecorder {
+        prompts: Mutex<Vec<(String, Option<String>)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl TranscriptionBackend for PromptRecorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn transcribe(
+            &self,
+            audio: &Path,
+            request: &TranscriptionRequest,
+        ) -> Result<Transcript> {
+            let name = audio.to_string_lossy().to_string();
+            if name == "broken" {
+                anyhow::bail!("upload failed");
+            }
+            self.prompts
+                .lock()
+                .unwrap()
+                .push((name.clone(), request.prompt.clone()));
+            Ok(Transcript::from_text(format!("Said {}.", name)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chained_chunks_continue_from_previous_transcript() {
+        let chunk = |name: &str| AudioChunk {
+            path: name.to_string(),
+            start: 0.0,
+            end: 0.0,
+        };
+        let chunks = [chunk("one"), chunk("broken"), chunk("three"), chunk("four")];
+        let backend = PromptRecorder::default();
+        let request = TranscriptionRequest::file("talk.mp3")
+            .prompt("Murmur.")
+            .build();
+        let config = Config {
+            chain_chunks: true,
+            ..Config::default()
+        };
+        let pipeline = ChunkPipeline::new(Path::new("talk.mp3"), &config, chunks.len());
+
+        // Later chunks wait for earlier ones, whatever order they start in
+        let transcribe = |i: usize| {
+            let (pipeline, backend, chunk, request) = (&pipeline, &backend, &chunks[i], &request);
+            async move {
+                let result = pipeline.transcribe(backend, chunk, i, request).await;
+                pipeline.hand_off(i, result.as_ref().ok());
+            }
+        };
+        futures::future::join_all((0..chunks.len()).rev().map(transcribe)).await;
+
+        let prompts = backend.prompts.into_inner().unwrap();
+        let prompt = |text: &str| Some(text.to_string());
+        assert_eq!(
+            prompts,
+            vec![
+                ("one".to_string(), prompt("Murmur.")),
+                // The failed chunk hands on nothing
+                ("three".to_string(), prompt("Murmur.")),
+                ("four".to_string(), prompt("Murmur. Said three.")),
+            ]
+        );
+
+        // Unchained chunks get the request's prompt as it is
+        let config = Config {
+            chain_chunks: false,
+            ..Config::default()
+        };
+        let pipeline = ChunkPipeline::new(Path::new("talk.mp3"), &config, chunks.len());
+        let request = pipeline.chunk_request(3, &request).await;
+        assert_eq!(request.prompt.as_deref(), Some("Murmur."));
+    }
+
+    /// Takes a while per chunk and counts how many chunks it had at once
+    #[derive(Default)]
+    struct OverlapCounter {
+        running: AtomicUsize,
+        most: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TranscriptionBackend for OverlapCounter {
+        fn name(&self) -> &str {
+            "overlap"
+        }
+
+        async fn transcribe(
+            &self,
+            _audio: &Path,
+            _request: &TranscriptionRequest,
+        ) -> Result<Transcript> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(Transcript::from_text("text"))
+        }
+    }
+
+    /// Most chunks the backend had at once when transcribing four of them
+    async fn most_chunks_at_once(chain_chunks: bool) -> usize {
+        let config = Config {
+            jobs: Jobs::Fixed(4),
+            chain_chunks,
+            ..Config::default()
+        };
+        let chunks: Vec<AudioChunk> = (0..4)
+            .map(|i| AudioChunk {
+                path: format!("chunk_{}.mp3", i),
+                start: 0.0,
+                end: 0.0,
+            })
+            .collect();
+        let backend = OverlapCounter::default();
+        let request = TranscriptionRequest::file("talk.mp3").build();
+        let pipeline = ChunkPipeline::new(Path::new("talk.mp3"), &config, chunks.len());
+
+        let transcribe = |i: usize| {
+            let (pipeline, backend, chunk, request) = (&pipeline, &backend, &chunks[i], &request);
+            async move {
+                let result = pipeline.transcribe(backend, chunk, i, request).await;
+                pipeline.hand_off(i, result.as_ref().ok());
+            }
+        };
+        futures::future::join_all((0..chunks.len()).map(transcribe)).await;
+        backend.most.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_unchained_chunks_overlap() {
+        assert!(!Config::default().chain_chunks);
+        assert_eq!(most_chunks_at_once(false).await, 4);
+        assert_eq!(most_chunks_at_once(true).await, 1);
+    }
+
+    /// Answers enhancement requests with the text to improve in upper case
+    struct UppercaseEnhancer;
+
+    impl wiremock::Respond for UppercaseEnhancer {
+        fn respond(&self, request: &wiremock::Request) -> wiremock::ResponseTemplate {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            let prompt = body["messages"][0]["content"].as_str().unwrap();
+            let text = match prompt.split_once("<part>\n") {
+                Some((_, rest)) => rest.split_once("\n</part>").unwrap().0,
+                None => prompt.split_once("\n\n").unwrap().1,
+            };
+            wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": text.to_uppercase()}}]
+            }))
+        }
+    }
+
+    async fn enhancement_processor(
+        server: &wiremock::MockServer,
+        cache: &Path,
+        mode: &str,
+    ) -> MurmurProcessor {
+        let config = Config {
+            enhancement_mode: mode.to_string(),
+            backend: BackendKind::OpenAiCompatible,
+            base_url: Some(server.uri()),
+            cache_dir: cache.to_path_buf(),
+            ..Config::default()
+        };
+        MurmurProcessor::with_config("key".to_string(), config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_long_transcript_enhanced_in_pieces() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/chat/completions"))
+            .respond_with(UppercaseEnhancer)
+            .expect(3)
+            .mount(&server)
+            .await;
+        let cache = tempfile::TempDir::new().unwrap();
+        let processor = enhancement_processor(&server, cache.path(), "clean").await;
+
+        let paragraph = "so this is a sentence about the project. ".repeat(80);
+        let text = format!("{}\n\n{}\n\n{}", paragraph, paragraph, paragraph);
+        assert!(text.len() > ENHANCEMENT_PIECE_BYTES * 2);
+
+        let request = TranscriptionRequest::microphone().build();
+        let enhanced = processor
+            .enhance_transcription(&text, &request, false)
+            .await
+            .unwrap();
+        assert_eq!(enhanced, text.trim().replace(" \n", "\n").to_uppercase());
+    }
+
+    #[tokio::test]
+    async fn test_enhancement_keeps_text_when_length_is_off() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/chat/completions"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"choices": [{"message": {"content": "A summary."}}]}),
+            ))
+            .mount(&server)
+            .await;
+        let cache = tempfile::TempDir::new().unwrap();
+        let processor = enhancement_processor(&server, cache.path(), "clean").await;
+
+        let text = "this went on for quite a while and nobody wrote it down. ".repeat(10);
+        let request = TranscriptionRequest::microphone().build();
+        let enhanced = processor
+            .enhance_transcription(&text, &request, false)
+            .await
+            .unwrap();
+        assert_eq!(enhanced, text.trim());
+    }
+
+    #[tokio::test]
+    async fn test_summary_sees_whole_transcript() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/chat/completions"))
+            .and(wiremock::matchers::body_string_contains("Summarize"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"choices": [{"message": {"content": "A summary."}}]}),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let cache = tempfile::TempDir::new().unwrap();
+        let processor = enhancement_processor(&server, cache.path(), "summary").await;
+
+        let text = "we talked about the roadmap for a long time. ".repeat(200);
+        assert!(text.len() > ENHANCEMENT_PIECE_BYTES);
+        let request = TranscriptionRequest::microphone().build();
+        let enhanced = processor
+            .enhance_transcription(&text, &request, false)
+            .await
+            .unwrap();
+        assert_eq!(enhanced, "A summary.");
+    }
+
+    #[tokio::test]
+    async fn test_file_mode_saves_enhanced_text_alongside() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            cache_dir: dir.path().join("cache"),
+            ..Config::default()
+        };
+        let processor = MurmurProcessor::with_config("key".to_string(), config).unwrap();
+        let input = dir.path().join("talk.mp3");
+        let request = TranscriptionRequest::file(&input).enhance(true).build();
+
+        let transcription = Transcription {
+            transcript: Transcript::from_text("um so the raw text"),
+            enhanced: Some("The enhanced text.".to_string()),
+        };
+        processor
+            .handle_output(&request, &transcription)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("talk.txt")).unwrap(),
+            "um so the raw text"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("talk.enhanced.txt")).unwrap(),
+            "The enhanced text."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_enhancement_keeps_raw_transcript() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/chat/completions"))
+            .respond_with(wiremock::ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            backend: BackendKind::OpenAiCompatible,
+            base_url: Some(server.uri()),
+            cache_dir: dir.path().join("cache"),
+            max_attempts: 2,
+            retry_base_delay_ms: 1,
+            ..Config::default()
+        };
+        let processor = MurmurProcessor::with_config("key".to_string(), config).unwrap();
+        let request = TranscriptionRequest::file(dir.path().join("talk.mp3"))
+            .enhance(true)
+            .build();
+
+        let transcription = Transcription {
+            enhanced: processor
+                .enhance_or_keep_raw("um so the raw text", &request)
+                .await,
+            transcript: Transcript::from_text("um so the raw text"),
+        };
+        assert_eq!(transcription.enhanced, None);
+
+        processor
+            .handle_output(&request, &transcription)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("talk.txt")).unwrap(),
+            "um so the raw text"
+        );
+        assert!(!dir.path().join("talk.enhanced.txt").exists());
+    }
+
+    #[test]
+    fn test_unknown_mode_fails_early() {
+        let config = Config {
+            enhancement_mode: "haiku".to_string(),
+            ..Config::default()
+        };
+        let error = MurmurProcessor::with_config("key".to_string(), config)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::UnsupportedInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_enhancement_without_api_key() {
+        let config = Config {
+            backend: BackendKind::WhisperCpp,
+            ..Config::default()
+        };
+        let processor = MurmurProcessor::with_config(String::new(), config.clone()).unwrap();
+
+        // Asking for enhancement fails before the file is even looked at
+        let request = TranscriptionRequest::file("missing.mp3")
+            .enhance(true)
+            .build();
+        let error = processor.process(&request).await.unwrap_err();
+        assert!(matches!(
+            MurmurError::find(&error),
+            Some(MurmurError::Auth(_))
+        ));
+
+        // Recordings are enhanced by default, and kept raw instead
+        let request = TranscriptionRequest::microphone().build();
+        assert!(!processor.should_enhance(&request).unwrap());
+
+        let processor = MurmurProcessor::with_config("key".to_string(), config).unwrap();
+        assert!(processor.should_enhance(&request).unwrap());
+    }
+
     #[test]
     fn test_args_recording_mode_when_no_input() {
         let args = Args {
             input: None,
             language: Some("en".to_string()),
+            ..Args::default()
         };
 
         assert_eq!(args.input, None);
@@ -365,6 +1404,7 @@ mod tests {
         let args = Args {
             input: Some(PathBuf::from("test.mp3")),
             language: Some("zh".to_string()),
+            ..Args::default()
         };
 
         assert_eq!(args.input, Some(PathBuf::from("test.mp3")));
@@ -1,28 +1,83 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Parser;
+use std::process::ExitCode;
 
-use murmur::{Args, MurmurProcessor};
+use murmur::{
+    error, vocabulary, Args, CacheManager, Command, ErrorReport, MurmurProcessor, Settings,
+    TranscriptionRequest, Vocabulary,
+};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    // Load .env first so it can provide MURMUR_* settings as well as the API key
+    dotenvy::dotenv().ok();
+
     let args = Args::parse();
 
     // Initialize logging with default settings
     env_logger::init();
 
-    // Load API key from environment
-    dotenvy::dotenv().ok();
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .context("OPENAI_API_KEY not found. Set it as an environment variable or in .env file")?;
+    let json_errors = args.json_errors;
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if json_errors {
+                let report = serde_json::to_string(&ErrorReport::new(&e))
+                    .unwrap_or_else(|_| format!("{{\"message\":{:?}}}", e.to_string()));
+                eprintln!("{}", report);
+            } else {
+                eprintln!("Error: {:?}", e);
+            }
+            ExitCode::from(error::exit_code(&e))
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<()> {
+    // Merge command line, environment and config file settings
+    let settings = Settings::load(args.config.as_deref())?;
+    let config = settings.build_config(&args);
+
+    if let Some(Command::Cache { action }) = &args.command {
+        return CacheManager::new(&config).run_command(action);
+    }
+
+    let api_key = match settings.api_key_source(&args).resolve() {
+        Ok(key) => key,
+        Err(_) if !config.backend.requires_api_key() => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    // Product names and acronyms from the global and project vocabulary files
+    // guide the spelling of the transcript
+    let vocabulary_files =
+        vocabulary::vocabulary_files(config.vocabulary_file.as_deref(), &std::env::current_dir()?);
+    let vocabulary = Vocabulary::load(&vocabulary_files)?;
+    let unused = vocabulary.unused_terms();
+    if !unused.is_empty() {
+        println!(
+            "Vocabulary too long for Whisper's prompt; leaving out {} terms from '{}' on",
+            unused.len(),
+            unused[0]
+        );
+    }
 
     // Create processor
-    let processor = MurmurProcessor::new(api_key)?;
+    let processor = MurmurProcessor::with_config(api_key, config)?;
+
+    // Continue an interrupted job with the settings it was started with
+    let mut request = TranscriptionRequest::from(&args);
+    request.prompt = vocabulary.prompt();
+    let request = match &args.command {
+        Some(Command::Resume { input }) => processor.resume_request(&request, input).await?,
+        _ => request,
+    };
 
     // Process the audio file or start voice recording/listening
-    let transcription = processor.process(&args).await?;
+    let transcription = processor.process(&request).await?;
 
-    // Handle output based on mode and arguments
-    processor.handle_output(&args, &transcription).await?;
+    // Handle output based on mode and request
+    processor.handle_output(&request, &transcription).await?;
 
     Ok(())
 }
@@ -0,0 +1,397 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+use crate::error::MurmurError;
+
+/// Extensions the transcription API accepts as uploads, with their MIME types
+const API_FORMATS: &[(&str, &str)] = &[
+    ("mp3", "audio/mpeg"),
+    ("mpga", "audio/mpeg"),
+    ("mpeg", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "audio/mp4"),
+    ("flac", "audio/flac"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("webm", "audio/webm"),
+];
+
+const FFMPEG_MISSING: &str = "FFmpeg is not installed. Install it, or build murmur \
+     with the `native-audio` feature to decode audio without it";
+
+/// Error for ffmpeg or ffprobe failing to start
+pub(crate) fn spawn_error(error: std::io::Error) -> anyhow::Error {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        anyhow::Error::new(error).context(MurmurError::ToolMissing(FFMPEG_MISSING.to_string()))
+    } else {
+        anyhow::Error::new(error).context("Failed to run ffmpeg")
+    }
+}
+
+/// Peak bitrate of `-q:a 2` VBR MP3, which is about 190 kbps on average
+const VBR_MP3_MAX_KBPS: u64 = 256;
+
+/// What decodes, slices and encodes audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioEngine {
+    /// The `ffmpeg` and `ffprobe` commands
+    Ffmpeg,
+    /// Built-in decoding to 16 kHz mono WAV
+    #[cfg(feature = "native-audio")]
+    Native,
+}
+
+impl AudioEngine {
+    /// FFmpeg when it is installed, otherwise the built-in decoder if murmur was
+    /// built with it
+    pub fn detect() -> Self {
+        static ENGINE: OnceLock<AudioEngine> = OnceLock::new();
+        *ENGINE.get_or_init(|| {
+            #[cfg(feature = "native-audio")]
+            if !ffmpeg_installed() {
+                return AudioEngine::Native;
+            }
+            AudioEngine::Ffmpeg
+        })
+    }
+
+    /// Extension of the audio files written with the given pre-processing
+    pub fn extension(&self, preprocess: Preprocess) -> &'static str {
+        match self {
+            AudioEngine::Ffmpeg => preprocess.extension(),
+            #[cfg(feature = "native-audio")]
+            AudioEngine::Native => "wav",
+        }
+    }
+
+    /// Most bytes a second of audio takes once written with the given pre-processing
+    pub fn encoded_bytes_per_second(&self, preprocess: Preprocess, bitrate_kbps: u32) -> u64 {
+        match self {
+            AudioEngine::Ffmpeg => preprocess.max_bytes_per_second(bitrate_kbps),
+            #[cfg(feature = "native-audio")]
+            AudioEngine::Native => crate::native_audio::WAV_BYTES_PER_SECOND,
+        }
+    }
+}
+
+/// Whether `ffmpeg` and `ffprobe` are on the `PATH`. This only looks for the
+/// files, so it doesn't block async callers on running them.
+#[cfg(feature = "native-audio")]
+fn ffmpeg_installed() -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    ["ffmpeg", "ffprobe"].iter().all(|tool| {
+        let file_name = format!("{}{}", tool, std::env::consts::EXE_SUFFIX);
+        std::env::split_paths(&path).any(|dir| dir.join(&file_name).is_file())
+    })
+}
+
+/// Optional encoding applied to audio before upload to make it smaller
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preprocess {
+    /// Upload audio as it is, re-encoding to high quality MP3 only when needed
+    #[default]
+    None,
+    /// Mono 16 kHz low-bitrate MP3
+    Mp3,
+    /// Mono 16 kHz low-bitrate Opus in an Ogg container
+    Opus,
+}
+
+impl Preprocess {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Preprocess::None)
+    }
+
+    /// Extension of files produced with this encoding
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Preprocess::None | Preprocess::Mp3 => "mp3",
+            Preprocess::Opus => "ogg",
+        }
+    }
+
+    /// Most bytes a second of audio takes with this encoding, whatever the
+    /// input's bitrate
+    pub fn max_bytes_per_second(&self, bitrate_kbps: u32) -> u64 {
+        let kbps = match self {
+            Preprocess::None => VBR_MP3_MAX_KBPS,
+            Preprocess::Mp3 | Preprocess::Opus => bitrate_kbps as u64,
+        };
+        kbps * 1000 / 8
+    }
+
+    /// FFmpeg output arguments for this encoding
+    pub fn ffmpeg_codec_args(&self, bitrate_kbps: u32) -> Vec<String> {
+        let args: &[&str] = match self {
+            // High quality VBR encoding (handles WAV and other input formats)
+            Preprocess::None => &["-c:a", "libmp3lame", "-q:a", "2"],
+            // Whisper works on 16 kHz mono internally, so nothing useful is lost
+            Preprocess::Mp3 => &["-ac", "1", "-ar", "16000", "-c:a", "libmp3lame"],
+            Preprocess::Opus => &[
+                "-ac",
+                "1",
+                "-ar",
+                "16000",
+                "-c:a",
+                "libopus",
+                "-application",
+                "voip",
+            ],
+        };
+
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        if self.is_enabled() {
+            args.extend(["-b:a".to_string(), format!("{}k", bitrate_kbps)]);
+        }
+        args
+    }
+}
+
+/// What ffprobe found in an input file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub format_name: String,
+    pub duration: Option<f64>,
+    pub has_audio: bool,
+    pub has_video: bool,
+}
+
+impl MediaInfo {
+    /// Whether the file can be uploaded as it is, or its audio has to be extracted first
+    pub fn needs_extraction(&self, path: &Path) -> bool {
+        self.has_video || !is_api_format(path)
+    }
+
+    fn from_ffprobe_json(json: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Output {
+            #[serde(default)]
+            streams: Vec<Stream>,
+            format: Format,
+        }
+
+        #[derive(Deserialize)]
+        struct Stream {
+            codec_type: String,
+            #[serde(default)]
+            disposition: Disposition,
+        }
+
+        #[derive(Deserialize, Default)]
+        struct Disposition {
+            #[serde(default)]
+            attached_pic: u8,
+        }
+
+        #[derive(Deserialize)]
+        struct Format {
+            format_name: String,
+            duration: Option<String>,
+        }
+
+        let output: Output = serde_json::from_str(json).context("Invalid ffprobe output")?;
+
+        // Cover art in audio files shows up as a video stream
+        let is_video = |s: &Stream| s.codec_type == "video" && s.disposition.attached_pic == 0;
+
+        Ok(Self {
+            format_name: output.format.format_name,
+            duration: output.format.duration.and_then(|d| d.parse().ok()),
+            has_audio: output.streams.iter().any(|s| s.codec_type == "audio"),
+            has_video: output.streams.iter().any(is_video),
+        })
+    }
+}
+
+/// Inspect a media file
+pub async fn probe(path: &Path) -> Result<MediaInfo> {
+    match AudioEngine::detect() {
+        AudioEngine::Ffmpeg => probe_with_ffprobe(path).await,
+        #[cfg(feature = "native-audio")]
+        AudioEngine::Native => {
+            let path = path.to_owned();
+            tokio::task::spawn_blocking(move || crate::native_audio::probe(&path)).await?
+        }
+    }
+}
+
+async fn probe_with_ffprobe(path: &Path) -> Result<MediaInfo> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=format_name,duration:stream=codec_type:stream_disposition=attached_pic",
+            "-of",
+            "json",
+            path.to_str().context("Invalid file path encoding")?,
+        ])
+        .output()
+        .await
+        .map_err(spawn_error)?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(MurmurError::UnsupportedInput(format!(
+            "Unsupported or unreadable media file {:?}: {}",
+            path,
+            error.trim()
+        ))
+        .into());
+    }
+
+    MediaInfo::from_ffprobe_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Re-encode the audio track of `input_path` into `output_path`, dropping any video
+pub async fn transcode_audio(
+    input_path: &Path,
+    output_path: &Path,
+    preprocess: Preprocess,
+    bitrate_kbps: u32,
+) -> Result<()> {
+    match AudioEngine::detect() {
+        AudioEngine::Ffmpeg => {}
+        #[cfg(feature = "native-audio")]
+        AudioEngine::Native => {
+            let (input_path, output_path) = (input_path.to_owned(), output_path.to_owned());
+            return tokio::task::spawn_blocking(move || {
+                crate::native_audio::extract(&input_path, &output_path, 0.0, None)
+            })
+            .await?;
+        }
+    }
+
+    let output = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-i",
+            input_path.to_str().context("Invalid file path encoding")?,
+            "-vn", // Drop video and cover art
+        ])
+        .args(preprocess.ffmpeg_codec_args(bitrate_kbps))
+        .args([
+            "-loglevel",
+            "error",
+            output_path.to_str().context("Invalid file path encoding")?,
+        ])
+        .output()
+        .await
+        .map_err(spawn_error)?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("FFmpeg error when transcoding audio: {}", error);
+    }
+
+    Ok(())
+}
+
+/// Whether the file extension is one the transcription API accepts
+pub fn is_api_format(path: &Path) -> bool {
+    api_format(path).is_some()
+}
+
+/// MIME type to send for an audio file, based on its extension
+pub fn mime_type(path: &Path) -> &'static str {
+    api_format(path).unwrap_or("application/octet-stream")
+}
+
+fn api_format(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    API_FORMATS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preprocess_codec_args() {
+        assert_eq!(
+            Preprocess::None.ffmpeg_codec_args(32),
+            vec!["-c:a", "libmp3lame", "-q:a", "2"]
+        );
+
+        let opus = Preprocess::Opus.ffmpeg_codec_args(24);
+        assert!(opus.windows(2).any(|w| w == ["-ac", "1"]));
+        assert!(opus.windows(2).any(|w| w == ["-ar", "16000"]));
+        assert!(opus.windows(2).any(|w| w == ["-b:a", "24k"]));
+        assert_eq!(Preprocess::Opus.extension(), "ogg");
+        assert_eq!(Preprocess::Mp3.extension(), "mp3");
+    }
+
+    #[test]
+    fn test_max_bytes_per_second() {
+        assert_eq!(Preprocess::None.max_bytes_per_second(32), 32_000);
+        assert_eq!(Preprocess::Mp3.max_bytes_per_second(32), 4_000);
+        assert_eq!(Preprocess::Opus.max_bytes_per_second(24), 3_000);
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type(Path::new("a.mp3")), "audio/mpeg");
+        assert_eq!(mime_type(Path::new("a.WAV")), "audio/wav");
+        assert_eq!(mime_type(Path::new("a.m4a")), "audio/mp4");
+        assert_eq!(mime_type(Path::new("a.mkv")), "application/octet-stream");
+        assert_eq!(mime_type(Path::new("noext")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_parse_audio_with_cover_art() {
+        let json = r#"{
+            "programs": [],
+            "streams": [
+                {"codec_type": "audio", "disposition": {"attached_pic": 0}},
+                {"codec_type": "video", "disposition": {"attached_pic": 1}}
+            ],
+            "format": {"format_name": "mp3", "duration": "183.4"}
+        }"#;
+
+        let info = MediaInfo::from_ffprobe_json(json).unwrap();
+        assert_eq!(info.format_name, "mp3");
+        assert_eq!(info.duration, Some(183.4));
+        assert!(info.has_audio);
+        assert!(!info.has_video);
+        assert!(!info.needs_extraction(Path::new("song.mp3")));
+    }
+
+    #[test]
+    fn test_parse_video() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "video", "disposition": {"attached_pic": 0}},
+                {"codec_type": "audio", "disposition": {"attached_pic": 0}}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "60.0"}
+        }"#;
+
+        let info = MediaInfo::from_ffprobe_json(json).unwrap();
+        assert!(info.has_audio);
+        assert!(info.has_video);
+        assert!(info.needs_extraction(Path::new("talk.mp4")));
+    }
+
+    #[test]
+    fn test_audio_in_unsupported_container_needs_extraction() {
+        let info = MediaInfo {
+            format_name: "ogg".to_string(),
+            duration: Some(1.0),
+            has_audio: true,
+            has_video: false,
+        };
+        assert!(info.needs_extraction(Path::new("voice.opus")));
+        assert!(!info.needs_extraction(Path::new("voice.ogg")));
+    }
+}
@@ -0,0 +1,446 @@
+//! In-process audio decoding for machines without FFmpeg (`native-audio` feature)
+//!
+//! Anything symphonia can demux and decode is read, downmixed to mono, resampled
+//! to 16 kHz and written as 16-bit WAV, which is what Whisper works with
+//! internally anyway. Encoding to MP3 or Opus still needs FFmpeg.
+
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecParameters, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+
+use crate::chunking::Silence;
+use crate::error::MurmurError;
+use crate::media::MediaInfo;
+
+/// Sample rate of the audio written
+pub const SAMPLE_RATE: u32 = 16_000;
+/// Size of the 16-bit mono WAV written, per second of audio
+pub const WAV_BYTES_PER_SECOND: u64 = SAMPLE_RATE as u64 * 2;
+/// Length of the frames compared against the silence threshold
+const SILENCE_FRAME_SECONDS: f64 = 0.01;
+
+/// Inspect a media file. The decoder only sees audio tracks, so video is never reported.
+pub fn probe(path: &Path) -> Result<MediaInfo> {
+    let input = AudioInput::open(path)?;
+    let format_name = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map_or_else(|| "unknown".to_string(), str::to_lowercase);
+
+    Ok(MediaInfo {
+        format_name,
+        duration: input.header_duration(),
+        has_audio: true,
+        has_video: false,
+    })
+}
+
+/// Length of the audio in seconds
+pub fn duration(path: &Path) -> Result<f64> {
+    AudioInput::open(path)?.duration()
+}
+
+/// Write `from..to` seconds of the input's audio to `output` as 16 kHz mono WAV,
+/// up to the end of the audio when `to` is `None`
+pub fn extract(input: &Path, output: &Path, from: f64, to: Option<f64>) -> Result<()> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(output, spec)
+        .with_context(|| format!("Failed to create {:?}", output))?;
+
+    AudioInput::open(input)?.decode(from, to, |samples| {
+        for &sample in samples {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        Ok(())
+    })?;
+
+    writer
+        .finalize()
+        .with_context(|| format!("Failed to write {:?}", output))
+}
+
+/// Find silences between `from` and `to`: stretches quieter than `noise_db`
+/// lasting at least `min_seconds`, like ffmpeg's `silencedetect`
+pub fn detect_silences(
+    input: &Path,
+    from: f64,
+    to: f64,
+    noise_db: f64,
+    min_seconds: f64,
+) -> Result<Vec<Silence>> {
+    let mut samples = Vec::new();
+    AudioInput::open(input)?.decode(from, Some(to), |decoded| {
+        samples.extend_from_slice(decoded);
+        Ok(())
+    })?;
+
+    Ok(find_silences(&samples, noise_db, min_seconds)
+        .into_iter()
+        .map(|silence| Silence {
+            start: silence.start + from,
+            end: silence.end + from,
+        })
+        .collect())
+}
+
+/// The first audio track of a file that can be decoded
+struct AudioInput {
+    reader: Box<dyn FormatReader>,
+    track_id: u32,
+    params: CodecParameters,
+}
+
+impl AudioInput {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let format_options = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let reader = symphonia::default::get_probe()
+            .format(&hint, stream, &format_options, &MetadataOptions::default())
+            .with_context(|| {
+                MurmurError::UnsupportedInput(format!(
+                    "Unsupported or unreadable media file {:?}",
+                    path
+                ))
+            })?
+            .format;
+
+        let track = reader
+            .tracks()
+            .iter()
+            .find(|track| is_decodable(&track.codec_params))
+            .with_context(|| {
+                MurmurError::UnsupportedInput(format!(
+                    "Input file {:?} has no audio stream murmur can decode",
+                    path
+                ))
+            })?;
+        let (track_id, params) = (track.id, track.codec_params.clone());
+
+        Ok(Self {
+            reader,
+            track_id,
+            params,
+        })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        // Only tracks with a sample rate are decodable
+        self.params.sample_rate.unwrap_or(SAMPLE_RATE)
+    }
+
+    /// Seconds from the start of the track at a timestamp
+    fn seconds(&self, ts: u64) -> f64 {
+        match self.params.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(ts);
+                time.seconds as f64 + time.frac
+            }
+            None => ts as f64 / self.sample_rate() as f64,
+        }
+    }
+
+    /// Length given in the file header, if there is one
+    fn header_duration(&self) -> Option<f64> {
+        self.params
+            .n_frames
+            .map(|frames| frames as f64 / self.sample_rate() as f64)
+    }
+
+    /// Length of the audio, read from the header or else by going through every packet
+    fn duration(mut self) -> Result<f64> {
+        if let Some(duration) = self.header_duration() {
+            return Ok(duration);
+        }
+
+        let mut end = 0;
+        loop {
+            match self.reader.next_packet() {
+                Ok(packet) if packet.track_id() == self.track_id => {
+                    end = end.max(packet.ts() + packet.dur());
+                }
+                Ok(_) => {}
+                Err(e) if is_end_of_stream(&e) => break,
+                Err(e) => return Err(e).context("Failed to read audio"),
+            }
+        }
+        Ok(self.seconds(end))
+    }
+
+    /// Decode `from..to` seconds as 16 kHz mono, handing samples to `sink` as
+    /// they become available
+    fn decode(
+        mut self,
+        from: f64,
+        to: Option<f64>,
+        mut sink: impl FnMut(&[f32]) -> Result<()>,
+    ) -> Result<()> {
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&self.params, &DecoderOptions::default())
+            .context("Unsupported audio codec")?;
+
+        if from > 0.0 {
+            let seek_to = SeekTo::Time {
+                time: Time::from(from),
+                track_id: Some(self.track_id),
+            };
+            // Streams that can't seek are decoded from the start instead
+            if self.reader.seek(SeekMode::Accurate, seek_to).is_ok() {
+                decoder.reset();
+            }
+        }
+
+        let sample_rate = self.sample_rate();
+        let mut resampler = Resampler::new(sample_rate, SAMPLE_RATE);
+        let mut mono = Vec::new();
+        let mut resampled = Vec::new();
+
+        loop {
+            let packet = match self.reader.next_packet() {
+                Ok(packet) => packet,
+                Err(e) if is_end_of_stream(&e) => break,
+                Err(e) => return Err(e).context("Failed to read audio"),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let packet_start = self.seconds(packet.ts());
+            if to.is_some_and(|to| packet_start >= to) {
+                break;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Damaged packets are skipped, as ffmpeg does
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(e).context("Failed to decode audio"),
+            };
+
+            let channels = decoded.spec().channels.count().max(1);
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+
+            mono.clear();
+            for (i, frame) in buffer.samples().chunks(channels).enumerate() {
+                let time = packet_start + i as f64 / sample_rate as f64;
+                if time >= from && to.is_none_or(|to| time < to) {
+                    mono.push(frame.iter().sum::<f32>() / channels as f32);
+                }
+            }
+
+            resampled.clear();
+            resampler.process(&mono, &mut resampled);
+            sink(&resampled)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_decodable(params: &CodecParameters) -> bool {
+    params.codec != CODEC_TYPE_NULL
+        && params.sample_rate.is_some()
+        && symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .is_some()
+}
+
+fn is_end_of_stream(error: &SymphoniaError) -> bool {
+    matches!(error, SymphoniaError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// Streaming sample rate converter. Each output sample is the average of the
+/// input samples it covers, which keeps aliasing low enough for speech; when
+/// upsampling, input samples are repeated.
+struct Resampler {
+    /// Input samples per output sample
+    step: f64,
+    position: f64,
+    next_output: f64,
+    sum: f32,
+    count: usize,
+    last: f32,
+}
+
+impl Resampler {
+    fn new(input_rate: u32, output_rate: u32) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        Self {
+            step,
+            position: 0.0,
+            next_output: step,
+            sum: 0.0,
+            count: 0,
+            last: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        for &sample in input {
+            self.sum += sample;
+            self.count += 1;
+            self.position += 1.0;
+
+            while self.position >= self.next_output {
+                if self.count > 0 {
+                    self.last = self.sum / self.count as f32;
+                    self.sum = 0.0;
+                    self.count = 0;
+                }
+                output.push(self.last);
+                self.next_output += self.step;
+            }
+        }
+    }
+}
+
+/// Stretches of 16 kHz audio quieter than `noise_db` for at least
+/// `min_seconds`, in seconds from the start of `samples`
+fn find_silences(samples: &[f32], noise_db: f64, min_seconds: f64) -> Vec<Silence> {
+    let frame_len = (SAMPLE_RATE as f64 * SILENCE_FRAME_SECONDS) as usize;
+    let threshold = 10f64.powf(noise_db / 20.0);
+    let seconds = |samples: usize| samples as f64 / SAMPLE_RATE as f64;
+
+    let mut silences = Vec::new();
+    let mut silence_start = None;
+    let mut close = |start: f64, end: f64| {
+        if end - start >= min_seconds {
+            silences.push(Silence { start, end });
+        }
+    };
+
+    for (i, frame) in samples.chunks(frame_len).enumerate() {
+        let power = frame.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / frame.len() as f64;
+        let time = seconds(i * frame_len);
+
+        if power.sqrt() < threshold {
+            silence_start.get_or_insert(time);
+        } else if let Some(start) = silence_start.take() {
+            close(start, time);
+        }
+    }
+    if let Some(start) = silence_start {
+        close(start, seconds(samples.len()));
+    }
+
+    silences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use tempfile::TempDir;
+
+    fn tone(seconds: f64, rate: u32) -> impl Iterator<Item = f32> {
+        (0..(seconds * rate as f64) as usize)
+            .map(move |i| 0.5 * (2.0 * PI * 440.0 * i as f32 / rate as f32).sin())
+    }
+
+    /// Stereo 44.1 kHz WAV: a second of tone, a second of silence, a second of tone
+    fn write_test_wav(path: &Path) {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44_100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        let samples = tone(1.0, 44_100)
+            .chain(std::iter::repeat_n(0.0, 44_100))
+            .chain(tone(1.0, 44_100));
+        for sample in samples {
+            let value = (sample * i16::MAX as f32) as i16;
+            writer.write_sample(value).unwrap();
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_resampler_rates() {
+        let mut output = Vec::new();
+        Resampler::new(48_000, SAMPLE_RATE).process(&[0.25; 4800], &mut output);
+        assert_eq!(output.len(), 1600);
+        assert!(output.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+
+        // Split input gives the same result as one block
+        let mut resampler = Resampler::new(44_100, SAMPLE_RATE);
+        let mut split = Vec::new();
+        resampler.process(&[0.5; 2205], &mut split);
+        resampler.process(&[0.5; 2205], &mut split);
+        assert_eq!(split.len(), 1600);
+
+        let mut upsampled = Vec::new();
+        Resampler::new(8_000, SAMPLE_RATE).process(&[0.1, 0.2], &mut upsampled);
+        assert_eq!(upsampled, vec![0.1, 0.1, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn test_find_silences() {
+        let samples: Vec<f32> = tone(1.0, SAMPLE_RATE)
+            .chain(std::iter::repeat_n(0.0, 8_000))
+            .chain(tone(0.5, SAMPLE_RATE))
+            // Too short to count
+            .chain(std::iter::repeat_n(0.0, 1_600))
+            .chain(tone(0.5, SAMPLE_RATE))
+            .collect();
+
+        let silences = find_silences(&samples, -30.0, 0.3);
+        assert_eq!(
+            silences,
+            vec![Silence {
+                start: 1.0,
+                end: 1.5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode_slice_and_detect_silence() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("input.wav");
+        write_test_wav(&input);
+
+        let info = probe(&input).unwrap();
+        assert_eq!(info.format_name, "wav");
+        assert!(info.has_audio && !info.has_video);
+        assert!((duration(&input).unwrap() - 3.0).abs() < 0.01);
+
+        let output = temp_dir.path().join("chunk.wav");
+        extract(&input, &output, 0.5, Some(2.0)).unwrap();
+        let reader = hound::WavReader::open(&output).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, SAMPLE_RATE);
+        assert!((reader.duration() as i64 - 24_000).abs() <= 2);
+
+        let silences = detect_silences(&input, 0.5, 2.5, -30.0, 0.5).unwrap();
+        assert_eq!(silences.len(), 1);
+        assert!((silences[0].start - 1.0).abs() < 0.02);
+        assert!((silences[0].end - 2.0).abs() < 0.02);
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::transcription::{Segment, Transcript};
+
+/// Extension of the enhanced transcript saved next to the raw one in file mode,
+/// e.g. `talk.enhanced.txt` next to `talk.txt`
+pub const ENHANCED_EXTENSION: &str = "enhanced.txt";
+
+/// Output format for the final transcript
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Plain text
+    #[default]
+    Text,
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles
+    Vtt,
+    /// JSON with the full text and timed segments
+    Json,
+}
+
+impl OutputFormat {
+    /// Whether the format needs segment timestamps from the API
+    pub fn needs_segments(&self) -> bool {
+        !matches!(self, OutputFormat::Text)
+    }
+
+    /// File extension used when saving the transcript
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// Render a transcript in this format
+    pub fn render(&self, transcript: &Transcript) -> Result<String> {
+        let rendered = match self {
+            OutputFormat::Text => transcript.text.clone(),
+            OutputFormat::Srt => render_srt(&transcript.segments),
+            OutputFormat::Vtt => render_vtt(&transcript.segments),
+            OutputFormat::Json => serde_json::to_string_pretty(transcript)? + "\n",
+        };
+        Ok(rendered)
+    }
+}
+
+fn render_srt(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                format_timestamp(segment.start, ','),
+                format_timestamp(segment.end, ','),
+                segment.text.trim()
+            )
+        })
+        .collect()
+}
+
+fn render_vtt(segments: &[Segment]) -> String {
+    let cues: String = segments
+        .iter()
+        .map(|segment| {
+            format!(
+                "{} --> {}\n{}\n\n",
+                format_timestamp(segment.start, '.'),
+                format_timestamp(segment.end, '.'),
+                segment.text.trim()
+            )
+        })
+        .collect();
+
+    format!("WEBVTT\n\n{}", cues)
+}
+
+/// Format seconds as `HH:MM:SS<sep>mmm`
+fn format_timestamp(seconds: f64, millis_separator: char) -> String {
+    let total_millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let hours = total_millis / 3_600_000;
+    let minutes = (total_millis % 3_600_000) / 60_000;
+    let secs = (total_millis % 60_000) / 1000;
+    let millis = total_millis % 1000;
+
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        hours, minutes, secs, millis_separator, millis
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_transcript() -> Transcript {
+        Transcript {
+            text: "Hello there. General Kenobi.".to_string(),
+            segments: vec![
+                Segment {
+                    start: 0.0,
+                    end: 1.5,
+                    text: " Hello there.".to_string(),
+                },
+                Segment {
+                    start: 3661.25,
+                    end: 3663.0,
+                    text: " General Kenobi.".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(format_timestamp(3661.25, '.'), "01:01:01.250");
+        assert_eq!(format_timestamp(-1.0, ','), "00:00:00,000");
+    }
+
+    #[test]
+    fn test_render_srt() {
+        let srt = OutputFormat::Srt.render(&sample_transcript()).unwrap();
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+             2\n01:01:01,250 --> 01:01:03,000\nGeneral Kenobi.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_render_vtt() {
+        let vtt = OutputFormat::Vtt.render(&sample_transcript()).unwrap();
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello there.\n\n"));
+    }
+
+    #[test]
+    fn test_render_json_round_trip() {
+        let json = OutputFormat::Json.render(&sample_transcript()).unwrap();
+        let parsed: Transcript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, sample_transcript());
+    }
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(OutputFormat::Text.extension(), "txt");
+        assert_eq!(OutputFormat::Srt.extension(), "srt");
+        assert!(!OutputFormat::Text.needs_segments());
+        assert!(OutputFormat::Vtt.needs_segments());
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+
+use crate::output::OutputFormat;
+use crate::Args;
+
+/// Where the audio to transcribe comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputSource {
+    /// An audio or video file
+    File(PathBuf),
+    /// A recording from the default microphone, stopped by the user
+    Microphone,
+}
+
+/// What to transcribe and how.
+///
+/// The library only deals in requests; the command line [`Args`] convert into
+/// one. Start with [`TranscriptionRequest::file`] or
+/// [`TranscriptionRequest::microphone`]:
+///
+/// ```
+/// use murmur::{OutputFormat, TranscriptionRequest};
+///
+/// let request = TranscriptionRequest::file("standup.m4a")
+///     .language("de")
+///     .format(OutputFormat::Srt)
+///     .build();
+/// assert_eq!(request.input_path().unwrap().to_str(), Some("standup.m4a"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptionRequest {
+    pub source: InputSource,
+    /// Language of the audio as an ISO-639-1 code; detected when `None`
+    pub language: Option<String>,
+    /// Transcription model, instead of the one in the config
+    pub model: Option<String>,
+    /// Text that guides the model's spelling and style
+    pub prompt: Option<String>,
+    pub format: OutputFormat,
+    /// Sampling temperature between 0 and 1 [default: 0]
+    pub temperature: Option<f32>,
+    /// Whether cached transcripts and enhancements may be reused
+    pub use_cache: bool,
+    /// Whether to enhance the transcript; by default recordings are enhanced
+    /// and files are not
+    pub enhance: Option<bool>,
+}
+
+impl TranscriptionRequest {
+    /// Transcribe an audio or video file
+    pub fn file(path: impl Into<PathBuf>) -> TranscriptionRequestBuilder {
+        TranscriptionRequestBuilder::new(InputSource::File(path.into()))
+    }
+
+    /// Record from the microphone and transcribe the recording
+    pub fn microphone() -> TranscriptionRequestBuilder {
+        TranscriptionRequestBuilder::new(InputSource::Microphone)
+    }
+
+    /// Whether the transcript gets enhanced
+    pub fn enhances(&self) -> bool {
+        self.enhance
+            .unwrap_or(self.source == InputSource::Microphone)
+    }
+
+    /// The input file, unless recording from the microphone
+    pub fn input_path(&self) -> Option<&Path> {
+        match &self.source {
+            InputSource::File(path) => Some(path),
+            InputSource::Microphone => None,
+        }
+    }
+}
+
+impl From<&Args> for TranscriptionRequest {
+    fn from(args: &Args) -> Self {
+        let mut builder = match &args.input {
+            Some(input) => TranscriptionRequest::file(input),
+            None => TranscriptionRequest::microphone(),
+        };
+        if let Some(language) = &args.language {
+            builder = builder.language(language);
+        }
+        if let Some(model) = &args.model {
+            builder = builder.model(model);
+        }
+        if args.enhance || args.no_enhance {
+            builder = builder.enhance(args.enhance);
+        }
+
+        builder
+            .format(args.format)
+            .use_cache(!args.no_cache)
+            .build()
+    }
+}
+
+/// Builder for [`TranscriptionRequest`]
+#[derive(Debug, Clone)]
+pub struct TranscriptionRequestBuilder {
+    request: TranscriptionRequest,
+}
+
+impl TranscriptionRequestBuilder {
+    fn new(source: InputSource) -> Self {
+        Self {
+            request: TranscriptionRequest {
+                source,
+                language: None,
+                model: None,
+                prompt: None,
+                format: OutputFormat::default(),
+                temperature: None,
+                use_cache: true,
+                enhance: None,
+            },
+        }
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.request.language = Some(language.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.request.model = Some(model.into());
+        self
+    }
+
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.request.prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.request.format = format;
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.request.temperature = Some(temperature);
+        self
+    }
+
+    pub fn use_cache(mut self, use_cache: bool) -> Self {
+        self.request.use_cache = use_cache;
+        self
+    }
+
+    pub fn enhance(mut self, enhance: bool) -> Self {
+        self.request.enhance = Some(enhance);
+        self
+    }
+
+    pub fn build(self) -> TranscriptionRequest {
+        self.request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_from_args() {
+        let args = Args {
+            input: Some(PathBuf::from("talk.mp3")),
+            language: Some("fr".to_string()),
+            format: OutputFormat::Vtt,
+            no_cache: true,
+            ..Args::default()
+        };
+
+        let request = TranscriptionRequest::from(&args);
+        assert_eq!(request.source, InputSource::File(PathBuf::from("talk.mp3")));
+        assert_eq!(request.language.as_deref(), Some("fr"));
+        assert_eq!(request.format, OutputFormat::Vtt);
+        assert!(!request.use_cache);
+        assert_eq!(request.prompt, None);
+
+        let request = TranscriptionRequest::from(&Args::default());
+        assert_eq!(request.source, InputSource::Microphone);
+        assert!(request.use_cache);
+    }
+
+    #[test]
+    fn test_enhance_defaults_to_recordings() {
+        assert!(TranscriptionRequest::microphone().build().enhances());
+        assert!(!TranscriptionRequest::file("talk.mp3").build().enhances());
+
+        let args = Args::try_parse_from(["murmur", "talk.mp3", "--enhance"]).unwrap();
+        assert!(TranscriptionRequest::from(&args).enhances());
+
+        let args = Args::try_parse_from(["murmur", "--no-enhance"]).unwrap();
+        assert!(!TranscriptionRequest::from(&args).enhances());
+
+        // The last flag wins
+        let args = Args::try_parse_from(["murmur", "--enhance", "--no-enhance"]).unwrap();
+        assert_eq!(TranscriptionRequest::from(&args).enhance, Some(false));
+    }
+
+    #[test]
+    fn test_builder() {
+        let request = TranscriptionRequest::microphone()
+            .model("gpt-4o-transcribe")
+            .prompt("Kubernetes, gRPC")
+            .temperature(0.2)
+            .use_cache(false)
+            .build();
+
+        assert_eq!(request.input_path(), None);
+        assert_eq!(request.model.as_deref(), Some("gpt-4o-transcribe"));
+        assert_eq!(request.prompt.as_deref(), Some("Kubernetes, gRPC"));
+        assert_eq!(request.temperature, Some(0.2));
+        assert_eq!(request.format, OutputFormat::Text);
+        assert!(!request.use_cache);
+    }
+}
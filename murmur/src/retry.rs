@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::MurmurError;
+use crate::utils::Config;
+
+/// Retry policy for API requests that fail with rate limits, server errors or network errors
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Rate-limited responses seen so far, shared between clones
+    pub rate_limited: Arc<AtomicU64>,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            max_delay: Duration::from_secs(config.retry_max_delay_seconds),
+            rate_limited: Arc::default(),
+        }
+    }
+
+    /// Number of rate-limited (429) responses seen so far
+    pub fn rate_limit_count(&self) -> u64 {
+        self.rate_limited.load(Ordering::Relaxed)
+    }
+
+    /// Whether a response with this status is worth retrying
+    pub fn is_retryable(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status.is_server_error()
+    }
+
+    /// Jittered exponential backoff for the given (1-based) failed attempt
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        // Equal jitter: keep half of the delay and randomize the other half
+        let half = delay / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+
+    /// Delay before retrying a failed response, honoring `Retry-After` when present
+    pub fn retry_delay(&self, response: &Response, attempt: u32) -> Duration {
+        response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after)
+            .map(|delay| delay.min(self.max_delay))
+            .unwrap_or_else(|| self.backoff_delay(attempt))
+    }
+
+    /// Send a request, rebuilding and retrying it while it fails with a retryable error.
+    ///
+    /// Returns the last response, which may still be an error response once all
+    /// attempts are used up.
+    pub async fn send<F>(&self, api_type: &str, build_request: F) -> Result<Response>
+    where
+        F: Fn() -> Result<RequestBuilder>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let delay = match build_request()?.send().await {
+                Ok(response) if Self::is_retryable(response.status()) => {
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if attempt >= self.max_attempts {
+                        return Ok(response);
+                    }
+                    self.retry_delay(&response, attempt)
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < self.max_attempts && (e.is_timeout() || e.is_connect()) => {
+                    self.backoff_delay(attempt)
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        MurmurError::Network(format!("Failed to send {} request", api_type))
+                    })
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given either in seconds or as an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(seconds as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            rate_limited: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(RetryPolicy::is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(RetryPolicy::is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!RetryPolicy::is_retryable(StatusCode::UNAUTHORIZED));
+        assert!(!RetryPolicy::is_retryable(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_backoff_delay_grows_and_is_capped() {
+        let policy = test_policy();
+
+        let first = policy.backoff_delay(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = policy.backoff_delay(3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+        let capped = policy.backoff_delay(20);
+        assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backend::BackendKind;
+use crate::concurrency::Jobs;
+use crate::error::MurmurError;
+use crate::media::Preprocess;
+use crate::utils::Config;
+use crate::Args;
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+pub const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// Settings read from the murmur config file
+///
+/// Every field is optional; values given on the command line or through
+/// `MURMUR_*` environment variables take precedence.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub backend: Option<BackendKind>,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub enhance_model: Option<String>,
+    pub mode: Option<String>,
+    pub templates_dir: Option<PathBuf>,
+    pub vocabulary_file: Option<PathBuf>,
+    pub max_attempts: Option<u32>,
+    pub jobs: Option<Jobs>,
+    pub chain_chunks: Option<bool>,
+    pub preprocess: Option<Preprocess>,
+    pub preprocess_bitrate: Option<u32>,
+    pub silence_detection: Option<bool>,
+    pub silence_noise_db: Option<f64>,
+    pub cache_dir: Option<PathBuf>,
+    pub api_key_env: Option<String>,
+    pub api_key_file: Option<PathBuf>,
+    pub api_key_cmd: Option<String>,
+}
+
+impl Settings {
+    /// Default config file location: `$XDG_CONFIG_HOME/murmur/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        crate::utils::xdg_dir("XDG_CONFIG_HOME", ".config")
+            .map(|dir| dir.join("murmur").join(CONFIG_FILE_NAME))
+    }
+
+    /// Load settings from `path`, or from the default location if no path is given.
+    /// A missing default config file is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config file {:?}", path))
+    }
+
+    /// Build the runtime config, letting command line arguments override the file
+    pub fn build_config(&self, args: &Args) -> Config {
+        let defaults = Config::default();
+        let backend = args.backend.or(self.backend).unwrap_or(defaults.backend);
+
+        Config {
+            backend,
+            base_url: args.base_url.clone().or_else(|| self.base_url.clone()),
+            transcription_model: args
+                .model
+                .clone()
+                .or_else(|| self.model.clone())
+                .unwrap_or(defaults.transcription_model.clone()),
+            enhancement_model: args
+                .enhance_model
+                .clone()
+                .or_else(|| self.enhance_model.clone())
+                .unwrap_or(defaults.enhancement_model.clone()),
+            enhancement_mode: args
+                .mode
+                .clone()
+                .or_else(|| self.mode.clone())
+                .unwrap_or(defaults.enhancement_mode.clone()),
+            templates_dir: self
+                .templates_dir
+                .clone()
+                .or(defaults.templates_dir.clone()),
+            vocabulary_file: self
+                .vocabulary_file
+                .clone()
+                .or(defaults.vocabulary_file.clone()),
+            max_attempts: args
+                .max_attempts
+                .or(self.max_attempts)
+                .unwrap_or(defaults.max_attempts),
+            jobs: args
+                .jobs
+                .or(self.jobs)
+                .unwrap_or_else(|| Jobs::default_for(backend)),
+            chain_chunks: match args.no_chain_chunks {
+                true => false,
+                false => args
+                    .chain_chunks
+                    .or(self.chain_chunks)
+                    .unwrap_or(defaults.chain_chunks),
+            },
+            preprocess: args
+                .preprocess
+                .or(self.preprocess)
+                .unwrap_or(defaults.preprocess),
+            preprocess_bitrate_kbps: args
+                .preprocess_bitrate
+                .or(self.preprocess_bitrate)
+                .unwrap_or(defaults.preprocess_bitrate_kbps),
+            silence_detection: self.silence_detection.unwrap_or(defaults.silence_detection),
+            silence_noise_db: self.silence_noise_db.unwrap_or(defaults.silence_noise_db),
+            cache_dir: self.cache_dir.clone().unwrap_or(defaults.cache_dir.clone()),
+            ..defaults
+        }
+    }
+
+    /// Pick where the API key comes from, command line arguments first
+    pub fn api_key_source(&self, args: &Args) -> ApiKeySource {
+        if let Some(cmd) = args.api_key_cmd.as_ref().or(self.api_key_cmd.as_ref()) {
+            return ApiKeySource::Command(cmd.clone());
+        }
+        if let Some(file) = args.api_key_file.as_ref().or(self.api_key_file.as_ref()) {
+            return ApiKeySource::File(file.clone());
+        }
+        let var = args
+            .api_key_env
+            .as_ref()
+            .or(self.api_key_env.as_ref())
+            .map_or(DEFAULT_API_KEY_ENV, String::as_str);
+        ApiKeySource::Env(var.to_string())
+    }
+}
+
+/// Where to read the API key from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeySource {
+    /// Name of an environment variable
+    Env(String),
+    /// File whose first line is the key
+    File(PathBuf),
+    /// Shell command printing the key, e.g. `pass show openai`
+    Command(String),
+}
+
+impl ApiKeySource {
+    /// Read the API key, failing if the source is missing or empty
+    pub fn resolve(&self) -> Result<String> {
+        let key = match self {
+            ApiKeySource::Env(var) => std::env::var(var).with_context(|| {
+                MurmurError::Auth(format!(
+                    "{} not found. Set it as an environment variable or in .env file",
+                    var
+                ))
+            })?,
+            ApiKeySource::File(path) => std::fs::read_to_string(path).with_context(|| {
+                MurmurError::Auth(format!("Failed to read API key file {:?}", path))
+            })?,
+            ApiKeySource::Command(cmd) => {
+                let output = Command::new("sh")
+                    .args(["-c", cmd])
+                    .output()
+                    .with_context(|| {
+                        MurmurError::Auth(format!("Failed to run API key command: {}", cmd))
+                    })?;
+                if !output.status.success() {
+                    return Err(MurmurError::Auth(format!(
+                        "API key command failed: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ))
+                    .into());
+                }
+                String::from_utf8(output.stdout).with_context(|| {
+                    MurmurError::Auth("API key command output is not UTF-8".to_string())
+                })?
+            }
+        };
+
+        let key = key.lines().next().unwrap_or_default().trim().to_string();
+        if key.is_empty() {
+            return Err(MurmurError::Auth(format!("API key from {:?} is empty", self)).into());
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings: Settings = toml::from_str(
+            r#"
+            backend = "openai-compatible"
+            base_url = "https://api.groq.com/openai/v1"
+            model = "whisper-large-v3"
+            api_key_env = "GROQ_API_KEY"
+            vocabulary_file = "/work/vocabulary.txt"
+            chain_chunks = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.backend, Some(BackendKind::OpenAiCompatible));
+        assert_eq!(settings.model.as_deref(), Some("whisper-large-v3"));
+        assert_eq!(settings.enhance_model, None);
+
+        let config = settings.build_config(&Args::default());
+        assert_eq!(
+            config.vocabulary_file,
+            Some(PathBuf::from("/work/vocabulary.txt"))
+        );
+        assert!(!config.chain_chunks);
+    }
+
+    #[test]
+    fn test_unknown_setting_rejected() {
+        assert!(toml::from_str::<Settings>("modle = \"whisper-1\"").is_err());
+    }
+
+    #[test]
+    fn test_args_override_settings() {
+        let settings = Settings {
+            base_url: Some("http://file:8000/v1".to_string()),
+            model: Some("file-model".to_string()),
+            ..Settings::default()
+        };
+        let args = Args {
+            model: Some("cli-model".to_string()),
+            mode: Some("email".to_string()),
+            ..Args::default()
+        };
+
+        let config = settings.build_config(&args);
+        assert_eq!(config.enhancement_mode, "email");
+        assert_eq!(config.backend, BackendKind::OpenAi);
+        assert_eq!(config.base_url.as_deref(), Some("http://file:8000/v1"));
+        assert_eq!(config.transcription_model, "cli-model");
+        assert_eq!(
+            config.enhancement_model,
+            Config::default().enhancement_model
+        );
+        assert_eq!(config.jobs, Jobs::Auto);
+    }
+
+    #[test]
+    fn test_chain_chunks_flags() {
+        let settings = Settings {
+            chain_chunks: Some(true),
+            ..Settings::default()
+        };
+        let chain = |argv: &[&str]| {
+            let args = Args::try_parse_from(argv).unwrap();
+            settings.build_config(&args).chain_chunks
+        };
+
+        assert!(
+            !Settings::default()
+                .build_config(&Args::default())
+                .chain_chunks
+        );
+        assert!(chain(&["murmur", "talk.mp3"]));
+        assert!(!chain(&["murmur", "talk.mp3", "--no-chain-chunks"]));
+        assert!(!chain(&["murmur", "--chain-chunks=false", "talk.mp3"]));
+        // The flag takes no value unless given with '=', and the last flag wins
+        assert!(chain(&[
+            "murmur",
+            "--no-chain-chunks",
+            "--chain-chunks",
+            "talk.mp3"
+        ]));
+        assert!(!chain(&["murmur", "--chain-chunks", "--no-chain-chunks"]));
+    }
+
+    #[test]
+    fn test_jobs_default_follows_backend() {
+        let settings = Settings {
+            backend: Some(BackendKind::WhisperCpp),
+            ..Settings::default()
+        };
+        assert_eq!(settings.build_config(&Args::default()).jobs, Jobs::Fixed(1));
+
+        let args = Args {
+            jobs: Some(Jobs::Fixed(3)),
+            ..Args::default()
+        };
+        assert_eq!(settings.build_config(&args).jobs, Jobs::Fixed(3));
+    }
+
+    #[test]
+    fn test_api_key_source_precedence() {
+        let settings = Settings {
+            api_key_file: Some(PathBuf::from("/file/key")),
+            ..Settings::default()
+        };
+        assert_eq!(
+            Settings::default().api_key_source(&Args::default()),
+            ApiKeySource::Env(DEFAULT_API_KEY_ENV.to_string())
+        );
+        assert_eq!(
+            settings.api_key_source(&Args::default()),
+            ApiKeySource::File(PathBuf::from("/file/key"))
+        );
+
+        let args = Args {
+            api_key_cmd: Some("echo key".to_string()),
+            ..Args::default()
+        };
+        assert_eq!(
+            settings.api_key_source(&args),
+            ApiKeySource::Command("echo key".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_api_key_from_file_and_command() {
+        let mut key_file = NamedTempFile::new().unwrap();
+        writeln!(key_file, "sk-from-file").unwrap();
+
+        let key = ApiKeySource::File(key_file.path().to_path_buf()).resolve();
+        assert_eq!(key.unwrap(), "sk-from-file");
+
+        let key = ApiKeySource::Command("printf 'sk-from-cmd\\n'".to_string()).resolve();
+        assert_eq!(key.unwrap(), "sk-from-cmd");
+
+        assert!(ApiKeySource::Command("true".to_string()).resolve().is_err());
+        assert!(ApiKeySource::Command("exit 1".to_string())
+            .resolve()
+            .is_err());
+    }
+
+    fn assert_auth_error(source: ApiKeySource) {
+        let error = source.resolve().unwrap_err();
+        assert!(
+            matches!(MurmurError::find(&error), Some(MurmurError::Auth(_))),
+            "{:?}",
+            error
+        );
+        assert_eq!(crate::error::exit_code(&error), 3);
+        assert_eq!(crate::ErrorReport::new(&error).kind, "auth");
+    }
+
+    #[test]
+    fn test_missing_api_key_file_is_auth_error() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_auth_error(ApiKeySource::File(dir.path().join("missing-key")));
+    }
+
+    #[test]
+    fn test_failing_api_key_command_is_auth_error() {
+        assert_auth_error(ApiKeySource::Command("echo locked >&2; exit 1".to_string()));
+        assert_auth_error(ApiKeySource::Command("printf '\\377'".to_string()));
+    }
+}
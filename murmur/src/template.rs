@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::error::MurmurError;
+
+pub const TEMPLATE_EXTENSION: &str = "txt";
+pub const DEFAULT_MODE: &str = "clean";
+
+/// Enhancement modes that ship with murmur
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("clean", include_str!("../templates/clean.txt")),
+    ("email", include_str!("../templates/email.txt")),
+    ("notes", include_str!("../templates/notes.txt")),
+    ("commit", include_str!("../templates/commit.txt")),
+    ("slack", include_str!("../templates/slack.txt")),
+    ("summary", include_str!("../templates/summary.txt")),
+];
+
+/// First line of a template whose output is the transcript itself, rewritten
+const REWRITE_MARKER: &str = "{rewrite}";
+
+/// Stands in for `{language}` when no language was given
+const ANY_LANGUAGE: &str = "the same language as the transcript";
+
+/// Prompt template of an enhancement mode.
+///
+/// Templates are plain text with `{transcript}`, `{language}` and `{date}`
+/// placeholders. `<name>.txt` in the templates directory adds a mode, or
+/// replaces the built-in mode of that name. A first line of `{rewrite}` marks
+/// a mode that rewrites the transcript rather than derives something from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub name: String,
+    body: String,
+    /// Whether the output is the transcript itself, cleaned up. Those modes
+    /// enhance long transcripts piece by piece and check the output length;
+    /// the others get the whole transcript at once.
+    pub rewrites: bool,
+}
+
+impl Template {
+    /// Load the template of mode `name`, preferring the user's own over the built-in one
+    pub fn load(name: &str, templates_dir: Option<&Path>) -> Result<Self> {
+        let is_valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        let user_file = templates_dir
+            .filter(|_| is_valid_name)
+            .map(|dir| dir.join(name).with_extension(TEMPLATE_EXTENSION))
+            .filter(|path| path.is_file());
+        let body = match user_file {
+            Some(path) => std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read template {:?}", path))?,
+            None => BUILTIN_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, body)| body.to_string())
+                .ok_or_else(|| {
+                    MurmurError::UnsupportedInput(format!(
+                        "Unknown enhancement mode '{}'. Available modes: {}",
+                        name,
+                        Self::available(templates_dir).join(", ")
+                    ))
+                })?,
+        };
+
+        Self::new(name, &body)
+    }
+
+    /// Template from its text, which has to contain a `{transcript}` placeholder
+    pub fn new(name: &str, body: &str) -> Result<Self> {
+        let (rewrites, body) = match body.split_once('\n') {
+            Some((first, rest)) if first.trim() == REWRITE_MARKER => (true, rest),
+            _ => (false, body),
+        };
+
+        if !body.contains("{transcript}") {
+            return Err(MurmurError::UnsupportedInput(format!(
+                "Template '{}' has no {{transcript}} placeholder",
+                name
+            ))
+            .into());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            body: body.trim_end().to_string(),
+            rewrites,
+        })
+    }
+
+    /// Names of the built-in modes and the templates in `templates_dir`, sorted
+    pub fn available(templates_dir: Option<&Path>) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        let entries = templates_dir.and_then(|dir| std::fs::read_dir(dir).ok());
+        for path in entries.into_iter().flatten().flatten().map(|e| e.path()) {
+            if path
+                .extension()
+                .is_some_and(|ext| ext == TEMPLATE_EXTENSION)
+            {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Fill in the placeholders. The transcript goes in last, so placeholders
+    /// spoken in the transcript stay as they are.
+    pub fn render(&self, transcript: &str, language: Option<&str>, date: &str) -> String {
+        self.body
+            .replace("{language}", language.unwrap_or(ANY_LANGUAGE))
+            .replace("{date}", date)
+            .replace("{transcript}", transcript)
+    }
+}
+
+/// Default templates directory: `$XDG_CONFIG_HOME/murmur/templates`
+pub fn default_templates_dir() -> Option<PathBuf> {
+    crate::utils::xdg_dir("XDG_CONFIG_HOME", ".config")
+        .map(|dir| dir.join("murmur").join("templates"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_templates() {
+        for (name, _) in BUILTIN_TEMPLATES {
+            assert!(Template::load(name, None).is_ok(), "{}", name);
+        }
+
+        let clean = Template::load(DEFAULT_MODE, None).unwrap();
+        assert!(clean.rewrites);
+        assert!(!Template::load("summary", None).unwrap().rewrites);
+
+        // The clean mode sends the prompt murmur always used, so cached
+        // enhancements stay valid
+        assert_eq!(
+            clean.render("hello world", Some("en"), "2026-01-01"),
+            "Please improve and format the following transcribed text. Fix any grammar issues, make it coherent, add proper punctuation, and make it more readable while preserving the original meaning. Output only the improved text without any explanations:\n\nhello world"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let template =
+            Template::new("memo", "Write in {language} on {date}:\n\n{transcript}\n").unwrap();
+
+        assert_eq!(
+            template.render("say {date} twice", Some("de"), "2026-10-18"),
+            "Write in de on 2026-10-18:\n\nsay {date} twice"
+        );
+        assert!(template
+            .render("hi", None, "2026-10-18")
+            .starts_with("Write in the same language as the transcript"));
+
+        assert!(Template::new("broken", "No placeholder").is_err());
+    }
+
+    #[test]
+    fn test_user_templates() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("standup.txt"),
+            "Standup notes:\n{transcript}",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("email.txt"), "My email: {transcript}").unwrap();
+        std::fs::write(dir.path().join("readme.md"), "not a template").unwrap();
+
+        let standup = Template::load("standup", Some(dir.path())).unwrap();
+        assert_eq!(standup.render("done", None, ""), "Standup notes:\ndone");
+        assert!(!standup.rewrites);
+
+        // User templates replace built-in ones
+        let email = Template::load("email", Some(dir.path())).unwrap();
+        assert_eq!(email.render("hi", None, ""), "My email: hi");
+
+        let available = Template::available(Some(dir.path()));
+        assert!(available.contains(&"standup".to_string()));
+
+        // User modes can rewrite too
+        std::fs::write(
+            dir.path().join("translate.txt"),
+            "{rewrite}\nTranslate to German:\n\n{transcript}\n",
+        )
+        .unwrap();
+        let translate = Template::load("translate", Some(dir.path())).unwrap();
+        assert!(translate.rewrites);
+        assert_eq!(
+            translate.render("hello", None, ""),
+            "Translate to German:\n\nhello"
+        );
+
+        // A replaced mode is whatever its replacement says
+        std::fs::write(dir.path().join("clean.txt"), "Tidy up: {transcript}").unwrap();
+        assert!(!Template::load("clean", Some(dir.path())).unwrap().rewrites);
+        assert!(!available.contains(&"readme".to_string()));
+        assert_eq!(available.iter().filter(|name| *name == "email").count(), 1);
+
+        let error = Template::load("haiku", Some(dir.path())).unwrap_err();
+        assert!(error.to_string().contains("standup"));
+        assert!(Template::load("../standup", Some(dir.path())).is_err());
+    }
+}
@@ -1,3 +1,68 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A timed piece of a transcript, in seconds from the start of the audio
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Transcription result; `segments` is empty unless timestamps were requested
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+}
+
+impl Transcript {
+    /// Plain text transcript without timing information
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Parse a `verbose_json` transcription response
+    pub fn from_verbose_json(body: &str) -> Result<Self> {
+        let mut transcript: Transcript =
+            serde_json::from_str(body).context("Failed to parse verbose_json transcription")?;
+        transcript.text = transcript.text.trim().to_string();
+        for segment in &mut transcript.segments {
+            segment.text = segment.text.trim().to_string();
+        }
+        Ok(transcript)
+    }
+
+    /// Move all segments by `offset` seconds, e.g. to a chunk's position in the full file
+    pub fn shifted(mut self, offset: f64) -> Self {
+        for segment in &mut self.segments {
+            segment.start += offset;
+            segment.end += offset;
+        }
+        self
+    }
+}
+
+/// Result of processing a request: the transcript, and the enhanced text if
+/// the request asked for enhancement
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcription {
+    pub transcript: Transcript,
+    pub enhanced: Option<String>,
+}
+
+/// Transcript of one audio chunk with the time range the chunk covers in the full audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkTranscript {
+    pub start: f64,
+    pub end: f64,
+    pub transcript: Transcript,
+}
+
 /// Transcript merging functionality with overlap detection
 #[derive(Default)]
 pub struct TranscriptMerger;
@@ -7,63 +72,260 @@ impl TranscriptMerger {
         Self
     }
 
-    /// Merge transcripts with duplicate removal and automatic overlap detection
-    pub fn merge_transcripts(&self, transcripts: Vec<String>) -> String {
-        if transcripts.is_empty() {
-            return String::new();
+    /// Merge chunk transcripts ordered by position in the full audio.
+    ///
+    /// Uses segment timestamps to resolve the overlap between neighbouring chunks
+    /// when every chunk has them, and falls back to text matching otherwise.
+    pub fn merge(&self, chunks: Vec<ChunkTranscript>) -> Transcript {
+        let has_timestamps = chunks.iter().any(|c| !c.transcript.segments.is_empty())
+            && chunks
+                .iter()
+                .all(|c| !c.transcript.segments.is_empty() || c.transcript.text.trim().is_empty());
+
+        if has_timestamps {
+            return self.merge_by_timestamps(chunks);
         }
 
-        if transcripts.len() == 1 {
-            return transcripts[0].clone();
+        // Only chunks whose audio overlaps the previous one share any text with it
+        let overlapping: Vec<bool> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| i > 0 && chunk.start < chunks[i - 1].end)
+            .collect();
+        let texts = chunks.into_iter().map(|c| c.transcript.text).collect();
+        Transcript::from_text(self.merge_texts(texts, &overlapping))
+    }
+
+    /// Merge timed chunks by cutting each overlap window in the middle: segments
+    /// centred before the cut come from the earlier chunk, the rest from the later one.
+    /// Both chunks are least reliable at their edges, where words may be cut off.
+    fn merge_by_timestamps(&self, chunks: Vec<ChunkTranscript>) -> Transcript {
+        let cuts: Vec<f64> = chunks
+            .windows(2)
+            .map(|pair| {
+                let (previous, current) = (&pair[0], &pair[1]);
+                if current.start < previous.end {
+                    (current.start + previous.end) / 2.0
+                } else {
+                    current.start
+                }
+            })
+            .collect();
+
+        let mut segments: Vec<Segment> = Vec::new();
+
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let lower = if i == 0 {
+                f64::NEG_INFINITY
+            } else {
+                cuts[i - 1]
+            };
+            let upper = cuts.get(i).copied().unwrap_or(f64::INFINITY);
+
+            let kept = chunk
+                .transcript
+                .shifted(chunk.start)
+                .segments
+                .into_iter()
+                .filter(|segment| {
+                    let centre = (segment.start + segment.end) / 2.0;
+                    centre >= lower && centre < upper
+                });
+            segments.extend(kept);
         }
 
-        let mut result = transcripts[0].clone();
+        let text = segments.iter().fold(String::new(), |text, segment| {
+            join_text(&text, &segment.text)
+        });
+
+        Transcript { text, segments }
+    }
+
+    /// Merge transcripts with duplicate removal and automatic overlap detection
+    pub fn merge_transcripts(&self, transcripts: Vec<String>) -> String {
+        let overlapping = vec![true; transcripts.len()];
+        self.merge_texts(transcripts, &overlapping)
+    }
+
+    /// Merge transcripts in order, aligning each one with the text before it
+    /// only when `overlapping[i]` says the audio of chunk `i` overlaps the previous chunk
+    fn merge_texts(&self, transcripts: Vec<String>, overlapping: &[bool]) -> String {
+        let mut result = String::new();
+
+        for (current, &overlaps) in transcripts.iter().zip(overlapping) {
+            let current = current.trim();
 
-        for current in transcripts.iter().skip(1) {
             // Skip empty transcripts
             if current.is_empty() {
                 continue;
             }
 
-            let overlap_size = self.find_overlap_size(&result, current);
+            result = match self.find_overlap(&result, current).filter(|_| overlaps) {
+                Some(overlap) => join_text(
+                    &result[..overlap.previous_end],
+                    &current[overlap.current_start..],
+                ),
+                None => join_text(&result, current),
+            };
+        }
 
-            if overlap_size == 0 {
-                // No overlap found, just append with a space
-                result.push(' ');
-                result.push_str(current);
-            } else {
-                // Append only the non-overlapping part of the current transcript
-                result.push_str(&current[overlap_size..]);
+        result
+    }
+
+    /// Find the best word-level alignment between the tail of `previous` and the
+    /// head of `current`.
+    ///
+    /// Words are compared case- and punctuation-insensitively, with an edit distance
+    /// over words so that small transcription differences between the two chunks
+    /// still align. Returns where to cut both texts, in the middle of the overlap.
+    fn find_overlap(&self, previous: &str, current: &str) -> Option<Overlap> {
+        const MAX_OVERLAP_WORDS: usize = 80; // Comfortably more than the grace period holds
+        const MAX_ERROR_RATE: f64 = 0.25; // Tolerated word edits relative to the overlap length
+
+        let previous_tokens = tokenize(previous);
+        let current_tokens = tokenize(current);
+        let previous_tail =
+            &previous_tokens[previous_tokens.len().saturating_sub(MAX_OVERLAP_WORDS)..];
+        let current_head = &current_tokens[..current_tokens.len().min(MAX_OVERLAP_WORDS)];
+
+        // (matched words, edits, previous tail start, current head length)
+        let mut best: Option<(usize, usize, usize, usize)> = None;
+
+        for start in 0..previous_tail.len() {
+            let distances = edit_distances(&previous_tail[start..], current_head);
+            let previous_len = previous_tail.len() - start;
+
+            for (current_len, &distance) in distances.iter().enumerate().skip(1) {
+                let longest = previous_len.max(current_len);
+                if distance as f64 > longest as f64 * MAX_ERROR_RATE {
+                    continue;
+                }
+
+                let matched = longest - distance;
+                let better = match best {
+                    Some((best_matched, best_distance, _, _)) => {
+                        matched > best_matched
+                            || (matched == best_matched && distance < best_distance)
+                    }
+                    None => true,
+                };
+                if better {
+                    best = Some((matched, distance, start, current_len));
+                }
             }
         }
 
-        result.trim().to_string()
+        let (_, _, start, current_len) = best?;
+        let previous_len = previous_tail.len() - start;
+
+        // Keep the first half of the overlap from `previous` and the second half from
+        // `current`, since each chunk is least reliable at its cut edge
+        let previous_keep = start + previous_len.div_ceil(2);
+        let current_skip = previous_len.div_ceil(2).min(current_len);
+
+        Some(Overlap {
+            previous_end: previous_tail
+                .get(previous_keep)
+                .map_or(previous.len(), |token| token.start),
+            current_start: current_head
+                .get(current_skip)
+                .map_or(current.len(), |token| token.start),
+        })
     }
+}
 
-    fn find_overlap_size(&self, previous: &str, current: &str) -> usize {
-        const MIN_OVERLAP: usize = 10; // Minimum number of characters to consider as overlap
-        const MAX_OVERLAP: usize = 300; // Maximum number of characters to check for overlap
+/// Byte positions where two overlapping texts should be cut and joined
+#[derive(Debug, PartialEq)]
+struct Overlap {
+    previous_end: usize,
+    current_start: usize,
+}
+
+/// A word (or a single CJK character) with its byte offset in the source text
+#[derive(Debug)]
+struct Token {
+    start: usize,
+    normalized: String,
+}
 
-        let max_check_size = MAX_OVERLAP.min(current.len()).min(previous.len());
+/// Split text into words for alignment. CJK text has no spaces, so every CJK
+/// character is its own token. Tokens consisting only of punctuation are dropped.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
 
-        // Check for different overlap sizes, starting from larger to smaller
-        for size in (MIN_OVERLAP..=max_check_size).rev() {
-            if self.has_overlap_at_size(previous, current, size) {
-                return size;
+    let mut finish = |token: Option<Token>| {
+        if let Some(token) = token.filter(|t| !t.normalized.is_empty()) {
+            tokens.push(token);
+        }
+    };
+
+    for (index, c) in text.char_indices() {
+        if c.is_whitespace() {
+            finish(current.take());
+        } else if is_cjk(c) {
+            finish(current.take());
+            let normalized = if c.is_alphanumeric() {
+                c.to_string()
+            } else {
+                String::new()
+            };
+            finish(Some(Token {
+                start: index,
+                normalized,
+            }));
+        } else {
+            let token = current.get_or_insert_with(|| Token {
+                start: index,
+                normalized: String::new(),
+            });
+            if c.is_alphanumeric() {
+                token.normalized.extend(c.to_lowercase());
             }
         }
+    }
+    finish(current.take());
 
-        0 // No overlap found
+    tokens
+}
+
+/// Edit distances between the whole of `a` and every prefix of `b`
+fn edit_distances(a: &[Token], b: &[Token]) -> Vec<usize> {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_token) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_token) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_token.normalized != b_token.normalized);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
     }
 
-    fn has_overlap_at_size(&self, previous: &str, current: &str, size: usize) -> bool {
-        let previous_suffix = previous
-            .chars()
-            .skip(previous.len() - size)
-            .collect::<String>();
-        let current_prefix = current.chars().take(size).collect::<String>();
+    row
+}
+
+/// CJK ideographs, kana and full-width punctuation, which are written without spaces
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff00}'..='\u{ffef}')
+}
+
+/// Join two pieces of transcript, with a space unless they meet at CJK text
+pub(crate) fn join_text(left: &str, right: &str) -> String {
+    let left = left.trim_end();
+    let right = right.trim_start();
 
-        previous_suffix == current_prefix
+    match (left.chars().last(), right.chars().next()) {
+        (None, _) => right.to_string(),
+        (_, None) => left.to_string(),
+        (Some(l), Some(r)) if is_cjk(l) || is_cjk(r) => format!("{}{}", left, right),
+        _ => format!("{} {}", left, right),
     }
 }
 
@@ -102,7 +364,7 @@ mod tests {
         let merger = create_merger();
         let transcripts = vec!["Hello world".to_string(), "world and universe".to_string()];
         let result = merger.merge_transcripts(transcripts);
-        assert_eq!(result, "Hello world world and universe");
+        assert_eq!(result, "Hello world and universe");
     }
 
     #[test]
@@ -110,7 +372,7 @@ mod tests {
         let merger = create_merger();
         let transcripts = vec!["Hello world ".to_string(), "world and universe".to_string()];
         let result = merger.merge_transcripts(transcripts);
-        assert_eq!(result, "Hello world  world and universe");
+        assert_eq!(result, "Hello world and universe");
     }
 
     #[test]
@@ -157,37 +419,200 @@ mod tests {
     }
 
     #[test]
-    fn test_find_overlap_size() {
+    fn test_merge_transcripts_fuzzy_overlap() {
+        let merger = create_merger();
+        // The same ten seconds transcribed differently in both chunks
+        let transcripts = vec![
+            "So the plan is simple. We ship the release on Monday, then we talk about hir-"
+                .to_string(),
+            "ship the release on monday. Then we talk about hiring and the budget.".to_string(),
+        ];
+        assert_eq!(
+            merger.merge_transcripts(transcripts),
+            "So the plan is simple. We ship the release on Monday, Then we talk about hiring and the budget."
+        );
+    }
+
+    #[test]
+    fn test_merge_transcripts_chinese() {
+        let merger = create_merger();
+        let transcripts = vec![
+            "今天我们讨论一下项目的进度，下周发布".to_string(),
+            "项目的进度，下周发布新版本。".to_string(),
+        ];
+        assert_eq!(
+            merger.merge_transcripts(transcripts),
+            "今天我们讨论一下项目的进度，下周发布新版本。"
+        );
+
+        let transcripts = vec!["你好".to_string(), "世界".to_string()];
+        assert_eq!(merger.merge_transcripts(transcripts), "你好世界");
+    }
+
+    #[test]
+    fn test_find_overlap() {
+        let merger = create_merger();
+
+        // Cut in the middle of a four word overlap
+        let overlap = merger
+            .find_overlap("Hello world test case", "world test case and universe")
+            .unwrap();
+        assert_eq!(
+            &"Hello world test case"[..overlap.previous_end],
+            "Hello world test "
+        );
+        assert_eq!(
+            &"world test case and universe"[overlap.current_start..],
+            "case and universe"
+        );
+
+        // Case and punctuation don't matter
+        assert!(merger
+            .find_overlap("We said HELLO, world!", "hello world again")
+            .is_some());
+
+        // No common words
+        assert_eq!(merger.find_overlap("Hello", "there"), None);
+        assert_eq!(merger.find_overlap("", "there"), None);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<String> = tokenize("Hello, World! -- 你好。ok")
+            .into_iter()
+            .map(|t| t.normalized)
+            .collect();
+        assert_eq!(tokens, vec!["hello", "world", "你", "好", "ok"]);
+    }
+
+    #[test]
+    fn test_join_text() {
+        assert_eq!(join_text("Hello ", " world"), "Hello world");
+        assert_eq!(join_text("", "world"), "world");
+        assert_eq!(join_text("你好，", "世界"), "你好，世界");
+    }
+
+    #[test]
+    fn test_merge_without_overlap_keeps_repeated_words() {
         let merger = create_merger();
+        let chunk = |start: f64, end: f64, text: &str| ChunkTranscript {
+            start,
+            end,
+            transcript: Transcript::from_text(text),
+        };
+
+        // Chunks cut at a boundary without overlap are joined as they are
+        let merged = merger.merge(vec![
+            chunk(0.0, 100.0, "I went to the"),
+            chunk(100.0, 200.0, "the store"),
+        ]);
+        assert_eq!(merged.text, "I went to the the store");
+    }
 
-        // Test with exact overlap (longer than minimum)
-        let overlap = merger.find_overlap_size("Hello world test", "world test and universe");
-        assert_eq!(overlap, 10); // "world test"
+    #[test]
+    fn test_from_verbose_json() {
+        let body = r#"{
+            "task": "transcribe",
+            "language": "english",
+            "duration": 4.2,
+            "text": " Hello there. General Kenobi.",
+            "segments": [
+                {"id": 0, "seek": 0, "start": 0.0, "end": 1.5, "text": " Hello there.", "tokens": [1, 2]},
+                {"id": 1, "seek": 0, "start": 2.0, "end": 4.2, "text": " General Kenobi.", "tokens": [3]}
+            ]
+        }"#;
+
+        let transcript = Transcript::from_verbose_json(body).unwrap();
+        assert_eq!(transcript.text, "Hello there. General Kenobi.");
+        assert_eq!(transcript.segments.len(), 2);
+        assert_eq!(transcript.segments[1].text, "General Kenobi.");
+        assert_eq!(transcript.segments[1].end, 4.2);
+    }
+
+    fn timed_chunk(start: f64, end: f64, segments: &[(f64, f64, &str)]) -> ChunkTranscript {
+        let segments: Vec<Segment> = segments
+            .iter()
+            .map(|&(start, end, text)| Segment {
+                start,
+                end,
+                text: text.to_string(),
+            })
+            .collect();
+        let text = segments
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        ChunkTranscript {
+            start,
+            end,
+            transcript: Transcript { text, segments },
+        }
+    }
 
-        // Test with no overlap
-        let overlap = merger.find_overlap_size("Hello", "there");
-        assert_eq!(overlap, 0);
+    #[test]
+    fn test_merge_shifts_segments_by_chunk_offset() {
+        let merger = create_merger();
 
-        // Test with short strings (below minimum overlap)
-        let overlap = merger.find_overlap_size("Hi", "i there");
-        assert_eq!(overlap, 0); // Below minimum overlap threshold
+        let merged = merger.merge(vec![
+            timed_chunk(0.0, 290.0, &[(1.0, 2.0, "first")]),
+            timed_chunk(290.0, 580.0, &[(1.0, 2.0, "second")]),
+        ]);
 
-        // Test with short overlap (below minimum)
-        let overlap = merger.find_overlap_size("Hello world", "world test");
-        assert_eq!(overlap, 0); // "world" is only 5 chars, below minimum of 10
+        assert_eq!(merged.text, "first second");
+        assert_eq!(merged.segments[0].start, 1.0);
+        assert_eq!(merged.segments[1].start, 291.0);
+        assert_eq!(merged.segments[1].end, 292.0);
     }
 
     #[test]
-    fn test_has_overlap_at_size() {
+    fn test_merge_drops_segments_in_overlap_window() {
         let merger = create_merger();
 
-        // Test exact match
-        assert!(merger.has_overlap_at_size("Hello world", "world test", 5));
+        // Chunks overlap between 90s and 110s, so the cut is at 100s.
+        // The same sentences were transcribed slightly differently in both chunks.
+        let merged = merger.merge(vec![
+            timed_chunk(
+                0.0,
+                110.0,
+                &[
+                    (80.0, 92.0, "We shipped the release."),
+                    (93.0, 104.0, "Next we talk about hiring"),
+                    (105.0, 110.0, "and the bud-"),
+                ],
+            ),
+            timed_chunk(
+                90.0,
+                200.0,
+                &[
+                    (0.0, 2.0, "release."),
+                    (3.0, 14.0, "Next, we talk about hiring"),
+                    (15.0, 22.0, "and the budget."),
+                ],
+            ),
+        ]);
 
-        // Test no match
-        assert!(!merger.has_overlap_at_size("Hello world", "test world", 5));
+        assert_eq!(
+            merged.text,
+            "We shipped the release. Next we talk about hiring and the budget."
+        );
+        let starts: Vec<f64> = merged.segments.iter().map(|s| s.start).collect();
+        assert_eq!(starts, vec![80.0, 93.0, 105.0]);
+        assert_eq!(merged.segments[2].end, 112.0);
+    }
 
-        // Test different size
-        assert!(merger.has_overlap_at_size("Hello world", "orld test", 4));
+    #[test]
+    fn test_merge_without_timestamps_uses_text() {
+        let merger = create_merger();
+        let chunk = |text: &str| ChunkTranscript {
+            start: 0.0,
+            end: 0.0,
+            transcript: Transcript::from_text(text),
+        };
+
+        let merged = merger.merge(vec![chunk("Hello"), chunk("world")]);
+        assert_eq!(merged.text, "Hello world");
+        assert!(merged.segments.is_empty());
     }
 }
@@ -2,35 +2,110 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::backend::BackendKind;
+use crate::concurrency::Jobs;
+use crate::error::MurmurError;
+use crate::media::{self, MediaInfo, Preprocess};
+use crate::output::OutputFormat;
+use crate::template;
+use crate::vocabulary;
 
 // Constants
 pub const WHISPER_TIMEOUT_SECONDS: u64 = 600;
+pub const MAX_ATTEMPTS: u32 = 4;
+pub const RETRY_BASE_DELAY_MS: u64 = 1000;
+pub const RETRY_MAX_DELAY_SECONDS: u64 = 60;
 pub const MAX_FILE_SIZE_MB: u64 = 25;
 pub const CHUNK_SIZE_MB: u64 = 20;
 pub const GRACE_PERIOD_SECONDS: u64 = 10;
+pub const DEFAULT_JOBS: usize = 4;
+pub const MAX_ADAPTIVE_JOBS: usize = 16;
+pub const EXTRACTION_JOBS: usize = 4;
+/// Longest enhancement reply, in tokens. Replies cut off here are an error.
+pub const ENHANCEMENT_MAX_TOKENS: u32 = 2000;
+/// Largest piece of transcript sent in one enhancement request, in bytes. The
+/// enhanced piece has to fit in [`ENHANCEMENT_MAX_TOKENS`], so this stays well below.
+pub const ENHANCEMENT_PIECE_BYTES: usize = 4000;
+pub const ENHANCEMENT_JOBS: usize = 4;
+pub const PREPROCESS_BITRATE_KBPS: u32 = 32;
+pub const SILENCE_NOISE_DB: f64 = -30.0;
+pub const SILENCE_MIN_SECONDS: f64 = 0.5;
+pub const SILENCE_SEARCH_SECONDS: u64 = 30;
 pub const TEMP_DIR_NAME: &str = "murmur_audio_chunks";
 pub const METADATA_FILE: &str = "metadata.json";
+pub const TRANSCRIPTION_MODEL: &str = "whisper-1";
+pub const ENHANCEMENT_MODEL: &str = "gpt-3.5-turbo";
 
 /// Configuration structure to centralize all constants and settings
 #[derive(Debug, Clone)]
 pub struct Config {
     pub whisper_timeout_seconds: u64,
+    /// Attempts per API request, including the first one
+    pub max_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_seconds: u64,
     pub max_file_size_mb: u64,
     pub chunk_size_mb: u64,
     pub grace_period_seconds: u64,
-    pub temp_dir_name: String,
+    /// How many chunks to transcribe at once
+    pub jobs: Jobs,
+    /// Prompt each chunk with the end of the previous chunk's transcript.
+    /// Chained chunks are transcribed one after another, whatever `jobs` says.
+    pub chain_chunks: bool,
+    /// Cut chunks at silences instead of at fixed sizes
+    pub silence_detection: bool,
+    /// Level below which audio counts as silence
+    pub silence_noise_db: f64,
+    pub silence_min_seconds: f64,
+    /// How far before the size target to look for a silence
+    pub silence_search_seconds: u64,
+    pub preprocess: Preprocess,
+    pub preprocess_bitrate_kbps: u32,
+    /// Root of the persistent chunk and transcript cache
+    pub cache_dir: PathBuf,
     pub metadata_file: String,
+    pub backend: BackendKind,
+    /// Overrides the backend's default base URL when set
+    pub base_url: Option<String>,
+    pub transcription_model: String,
+    pub enhancement_model: String,
+    /// Name of the enhancement template to use
+    pub enhancement_mode: String,
+    /// Directory with the user's own enhancement templates
+    pub templates_dir: Option<PathBuf>,
+    /// Global vocabulary file, used alongside the project's own
+    pub vocabulary_file: Option<PathBuf>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             whisper_timeout_seconds: WHISPER_TIMEOUT_SECONDS,
+            max_attempts: MAX_ATTEMPTS,
+            retry_base_delay_ms: RETRY_BASE_DELAY_MS,
+            retry_max_delay_seconds: RETRY_MAX_DELAY_SECONDS,
             max_file_size_mb: MAX_FILE_SIZE_MB,
             chunk_size_mb: CHUNK_SIZE_MB,
             grace_period_seconds: GRACE_PERIOD_SECONDS,
-            temp_dir_name: TEMP_DIR_NAME.to_string(),
+            jobs: Jobs::default_for(BackendKind::default()),
+            chain_chunks: false,
+            silence_detection: true,
+            silence_noise_db: SILENCE_NOISE_DB,
+            silence_min_seconds: SILENCE_MIN_SECONDS,
+            silence_search_seconds: SILENCE_SEARCH_SECONDS,
+            preprocess: Preprocess::default(),
+            preprocess_bitrate_kbps: PREPROCESS_BITRATE_KBPS,
+            cache_dir: default_cache_dir(),
             metadata_file: METADATA_FILE.to_string(),
+            backend: BackendKind::default(),
+            base_url: None,
+            transcription_model: TRANSCRIPTION_MODEL.to_string(),
+            enhancement_model: ENHANCEMENT_MODEL.to_string(),
+            enhancement_mode: template::DEFAULT_MODE.to_string(),
+            templates_dir: template::default_templates_dir(),
+            vocabulary_file: vocabulary::default_vocabulary_file(),
         }
     }
 }
@@ -44,19 +119,65 @@ impl Config {
         self.chunk_size_mb * 1024 * 1024
     }
 
+    /// Scratch directory for chunks that are not cached, private to this process
     pub fn temp_dir_path(&self) -> PathBuf {
-        std::env::temp_dir().join(&self.temp_dir_name)
+        std::env::temp_dir().join(format!("{}_{}", TEMP_DIR_NAME, std::process::id()))
     }
 }
 
-/// File metadata structure for caching
-#[derive(Serialize, Deserialize, Debug)]
+/// Default cache location: `$XDG_CACHE_HOME/murmur`, or the system temp dir without a home
+pub fn default_cache_dir() -> PathBuf {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+        .unwrap_or_else(std::env::temp_dir)
+        .join("murmur")
+}
+
+/// Manifest of a large-file job, kept in its cache directory so an
+/// interrupted job can be resumed
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMetadata {
     pub original_filename: String,
     pub original_size: u64,
     pub original_hash: String,
     pub chunk_count: usize,
     pub creation_time: u64,
+    /// Language of the run that started the job, reused by `murmur resume`
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Output format of the run that started the job, reused by `murmur resume`
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub chunks: Vec<ChunkRecord>,
+}
+
+impl FileMetadata {
+    pub fn completed_chunks(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.status == ChunkStatus::Done)
+            .count()
+    }
+}
+
+/// Progress of one chunk of a large-file job
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkRecord {
+    pub path: String,
+    pub start: f64,
+    pub end: f64,
+    pub status: ChunkStatus,
+    /// Transcription attempts over all runs; retries of a single request count once
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStatus {
+    Pending,
+    Done,
+    Failed,
 }
 
 /// Calculate file hash using SHA256
@@ -77,34 +198,70 @@ pub fn bytes_to_mb(bytes: u64) -> f64 {
     bytes as f64 / (1024.0 * 1024.0)
 }
 
-/// Validate that input file exists, is readable, and is an MP3 file
-pub async fn validate_input_file(file_path: &Path) -> Result<()> {
+/// Validate that input file exists and is a media file with an audio stream
+pub async fn validate_input_file(file_path: &Path) -> Result<MediaInfo> {
     if !file_path.exists() {
-        anyhow::bail!("Input file {:?} does not exist", file_path);
+        return Err(MurmurError::UnsupportedInput(format!(
+            "Input file {:?} does not exist",
+            file_path
+        ))
+        .into());
     }
 
-    // Check file extension
-    match file_path.extension().and_then(|ext| ext.to_str()) {
-        Some(ext) if ext.to_lowercase() == "mp3" => {}
-        Some(ext) => anyhow::bail!(
-            "Unsupported file format: .{}. Only MP3 files are supported.",
-            ext
-        ),
-        None => anyhow::bail!("File has no extension. Only MP3 files are supported."),
+    let media_info = media::probe(file_path).await?;
+    if !media_info.has_audio {
+        return Err(MurmurError::UnsupportedInput(format!(
+            "Input file {:?} has no audio stream",
+            file_path
+        ))
+        .into());
     }
 
-    Ok(())
+    Ok(media_info)
 }
 
-/// Save transcription to file
-pub async fn save_transcription(input_path: &Path, content: &str) -> Result<PathBuf> {
-    let output_path = input_path.with_extension("txt");
-    tokio::fs::write(&output_path, content)
+/// Save transcription next to the input file with the given extension
+pub async fn save_transcription(
+    input_path: &Path,
+    content: &str,
+    extension: &str,
+) -> Result<PathBuf> {
+    let output_path = input_path.with_extension(extension);
+    write_atomic(&output_path, content)
         .await
         .context("Failed to write output file")?;
     Ok(output_path)
 }
 
+/// Write a file through a temporary file in the same directory that is renamed
+/// into place, so a crash never leaves a partially written file behind
+pub async fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid file path {:?}", path))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    ));
+
+    let result = async {
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(content.as_ref()).await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        fs::remove_file(&temp_path).await.ok();
+    }
+    result.with_context(|| format!("Failed to write {:?}", path))
+}
+
 /// Extract filename from path with fallback
 pub fn get_filename_or_default(file_path: &Path, default: &str) -> String {
     file_path
@@ -114,6 +271,14 @@ pub fn get_filename_or_default(file_path: &Path, default: &str) -> String {
         .to_string()
 }
 
+/// Resolve an XDG base directory, falling back to `~/<home_relative>`
+pub fn xdg_dir(env_var: &str, home_relative: &str) -> Option<PathBuf> {
+    std::env::var_os(env_var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(home_relative)))
+}
+
 /// Get current timestamp in seconds since epoch
 pub fn current_timestamp() -> u64 {
     std::time::SystemTime::now()
@@ -182,10 +347,7 @@ impl ProgressDisplay {
 
     /// Display progress for parallel chunk processing
     pub fn show_parallel_progress(completed: usize, total: usize) {
-        StatusLineManager::update_status(&format!(
-            "\x1b[KProcessing... {}/{}",
-            completed, total
-        ));
+        StatusLineManager::update_status(&format!("\x1b[KProcessing... {}/{}", completed, total));
     }
 
     /// Clear progress display
@@ -247,7 +409,7 @@ mod tests {
         let path = temp_file.path().to_path_buf();
 
         let content = "Test transcription content";
-        let result = save_transcription(&path, content).await;
+        let result = save_transcription(&path, content, "txt").await;
 
         assert!(result.is_ok());
         let output_path = result.unwrap();
@@ -258,4 +420,17 @@ mod tests {
 
         tokio::fs::remove_file(output_path).await.ok();
     }
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_without_leftovers() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.txt");
+
+        write_atomic(&path, "first version").await.unwrap();
+        write_atomic(&path, "second").await.unwrap();
+
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "second");
+        let files = std::fs::read_dir(temp_dir.path()).unwrap().count();
+        assert_eq!(files, 1, "No temporary files should be left behind");
+    }
 }
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+pub const VOCABULARY_FILE_NAME: &str = "vocabulary.txt";
+pub const PROJECT_VOCABULARY_FILE_NAME: &str = ".murmur-vocabulary.txt";
+
+/// Characters of a chunk's transcript handed to the next chunk as its prompt.
+/// Whisper only reads the last 224 tokens of a prompt, so together with
+/// [`MAX_VOCABULARY_CHARS`] this stays within about 200 tokens of English.
+pub const PREVIOUS_TEXT_CHARS: usize = 300;
+
+/// Longest vocabulary prompt. Whisper would drop the leading terms of a longer
+/// prompt, so terms past this are left out instead, with a warning.
+pub const MAX_VOCABULARY_CHARS: usize = 500;
+
+/// Words and spellings the transcription should stick to, such as product
+/// names and acronyms.
+///
+/// Vocabulary files list one term per line; blank lines and lines starting
+/// with `#` are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vocabulary {
+    terms: Vec<String>,
+}
+
+impl Vocabulary {
+    /// Read and merge the vocabulary files, dropping repeated terms
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut vocabulary = Self::default();
+        for path in paths {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read vocabulary file {:?}", path))?;
+            vocabulary.extend(content.lines());
+        }
+        Ok(vocabulary)
+    }
+
+    fn extend<'a>(&mut self, lines: impl IntoIterator<Item = &'a str>) {
+        for term in lines.into_iter().map(str::trim) {
+            if term.is_empty() || term.starts_with('#') {
+                continue;
+            }
+            if !self.terms.iter().any(|known| known == term) {
+                self.terms.push(term.to_string());
+            }
+        }
+    }
+
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    /// Terms that don't fit in the prompt any more, the last ones listed
+    pub fn unused_terms(&self) -> &[String] {
+        &self.terms[self.prompt_terms()..]
+    }
+
+    /// The terms as a Whisper prompt, or `None` without any terms
+    pub fn prompt(&self) -> Option<String> {
+        let terms = &self.terms[..self.prompt_terms()];
+        if terms.is_empty() {
+            return None;
+        }
+        Some(format!("{}.", terms.join(", ")))
+    }
+
+    /// How many of the terms fit in [`MAX_VOCABULARY_CHARS`] as `a, b, c.`
+    fn prompt_terms(&self) -> usize {
+        let mut length = 0;
+        self.terms
+            .iter()
+            .take_while(|term| {
+                length += term.chars().count() + 2;
+                length - 1 <= MAX_VOCABULARY_CHARS
+            })
+            .count()
+    }
+}
+
+/// Vocabulary files that apply in `dir`: the global one, then the nearest
+/// `.murmur-vocabulary.txt` in `dir` or its parents. Missing files are left out.
+pub fn vocabulary_files(global: Option<&Path>, dir: &Path) -> Vec<PathBuf> {
+    let project = dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_VOCABULARY_FILE_NAME))
+        .find(|path| path.is_file());
+
+    global
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(project)
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Default global vocabulary file: `$XDG_CONFIG_HOME/murmur/vocabulary.txt`
+pub fn default_vocabulary_file() -> Option<PathBuf> {
+    crate::utils::xdg_dir("XDG_CONFIG_HOME", ".config")
+        .map(|dir| dir.join("murmur").join(VOCABULARY_FILE_NAME))
+}
+
+/// Prompt for a chunk: the request's prompt followed by the end of the
+/// previous chunk's transcript, cut at a word boundary when it has words
+pub fn chunk_prompt(prompt: Option<&str>, previous_text: &str) -> Option<String> {
+    let previous_text = previous_text.trim();
+    let start = previous_text
+        .char_indices()
+        .rev()
+        .nth(PREVIOUS_TEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let tail = match (
+        start,
+        previous_text[start..].split_once(char::is_whitespace),
+    ) {
+        (0, _) => previous_text,
+        // Skip the word the cut landed in
+        (_, Some((_, rest))) => rest.trim_start(),
+        // Text without spaces, such as Chinese, is cut between characters
+        (_, None) => &previous_text[start..],
+    };
+
+    match (prompt, tail) {
+        (prompt, "") => prompt.map(str::to_string),
+        (None, tail) => Some(tail.to_string()),
+        (Some(prompt), tail) => Some(format!("{} {}", prompt, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_merges_files() {
+        let dir = TempDir::new().unwrap();
+        let global = dir.path().join("global.txt");
+        let project = dir.path().join("project.txt");
+        std::fs::write(&global, "# Products\nMurmur\n\n  Kubernetes \n").unwrap();
+        std::fs::write(&project, "gRPC\nMurmur\n").unwrap();
+
+        let vocabulary = Vocabulary::load(&[global, project]).unwrap();
+        assert_eq!(vocabulary.terms(), ["Murmur", "Kubernetes", "gRPC"]);
+        assert_eq!(
+            vocabulary.prompt().as_deref(),
+            Some("Murmur, Kubernetes, gRPC.")
+        );
+
+        assert!(vocabulary.unused_terms().is_empty());
+        assert_eq!(Vocabulary::default().prompt(), None);
+        assert!(Vocabulary::load(&[dir.path().join("missing.txt")]).is_err());
+    }
+
+    #[test]
+    fn test_prompt_fits_whisper() {
+        let mut vocabulary = Vocabulary::default();
+        let terms: Vec<String> = (0..100).map(|i| format!("Product{:03}", i)).collect();
+        vocabulary.extend(terms.iter().map(String::as_str));
+
+        let prompt = vocabulary.prompt().unwrap();
+        assert!(prompt.chars().count() <= MAX_VOCABULARY_CHARS);
+        assert!(prompt.starts_with("Product000, Product001"));
+        assert!(prompt.ends_with('.'));
+
+        // The terms left out are the last ones, and none is lost silently
+        let unused = vocabulary.unused_terms();
+        assert!(!unused.is_empty());
+        assert_eq!(unused.last(), terms.last());
+        let used = prompt.trim_end_matches('.').split(", ").count();
+        assert_eq!(used + unused.len(), terms.len());
+    }
+
+    #[test]
+    fn test_vocabulary_files() {
+        let dir = TempDir::new().unwrap();
+        let global = dir.path().join(VOCABULARY_FILE_NAME);
+        let project_dir = dir.path().join("project");
+        let nested = project_dir.join("docs").join("meetings");
+        std::fs::create_dir_all(&nested).unwrap();
+        let project = project_dir.join(PROJECT_VOCABULARY_FILE_NAME);
+        std::fs::write(&project, "Murmur").unwrap();
+
+        // The nearest project file is found from nested directories
+        assert_eq!(
+            vocabulary_files(Some(&global), &nested),
+            vec![project.clone()]
+        );
+
+        std::fs::write(&global, "OpenAI").unwrap();
+        assert_eq!(
+            vocabulary_files(Some(&global), &nested),
+            vec![global.clone(), project]
+        );
+        assert_eq!(vocabulary_files(None, dir.path()), Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn test_chunk_prompt() {
+        assert_eq!(chunk_prompt(None, "  "), None);
+        assert_eq!(
+            chunk_prompt(Some("Murmur."), "").as_deref(),
+            Some("Murmur.")
+        );
+        assert_eq!(
+            chunk_prompt(Some("Murmur."), "We shipped it.").as_deref(),
+            Some("Murmur. We shipped it.")
+        );
+
+        // Long transcripts hand on whole words from their end
+        let previous = "word ".repeat(200) + "and the last sentence.";
+        let prompt = chunk_prompt(None, &previous).unwrap();
+        assert!(prompt.len() <= PREVIOUS_TEXT_CHARS);
+        assert!(prompt.starts_with("word "));
+        assert!(prompt.ends_with("and the last sentence."));
+
+        // Chinese has no spaces to cut at
+        let previous = "我们今天讨论了项目的进度。".repeat(40);
+        let prompt = chunk_prompt(Some("Murmur."), &previous).unwrap();
+        let tail = prompt.strip_prefix("Murmur. ").unwrap();
+        assert_eq!(tail.chars().count(), PREVIOUS_TEXT_CHARS);
+        assert!(previous.ends_with(tail));
+    }
+}
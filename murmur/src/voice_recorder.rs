@@ -13,6 +13,8 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use crate::error::MurmurError;
+
 pub struct VoiceRecorder {
     device: Device,
     config: StreamConfig,
@@ -21,13 +23,17 @@ pub struct VoiceRecorder {
 impl VoiceRecorder {
     pub fn new() -> Result<Self> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("No input device available. Please check microphone permissions.")?;
+        let device = host.default_input_device().with_context(|| {
+            MurmurError::AudioDevice(
+                "No input device available. Please check microphone permissions.".to_string(),
+            )
+        })?;
 
         let config = device
             .default_input_config()
-            .context("Failed to get default input config")?
+            .with_context(|| {
+                MurmurError::AudioDevice("Failed to get default input config".to_string())
+            })?
             .into();
 
         Ok(Self { device, config })
@@ -183,9 +189,13 @@ impl VoiceRecorder {
                 |err| eprintln!("Audio stream error: {}", err),
                 None,
             )
-            .context("Failed to build input stream")?;
+            .with_context(|| {
+                MurmurError::AudioDevice("Failed to build input stream".to_string())
+            })?;
 
-        stream.play().context("Failed to start audio stream")?;
+        stream.play().with_context(|| {
+            MurmurError::AudioDevice("Failed to start audio stream".to_string())
+        })?;
         Ok(stream)
     }
 